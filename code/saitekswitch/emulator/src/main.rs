@@ -0,0 +1,365 @@
+/*
+Terminal emulator for the Saitek Switch Panel
+
+MIT License
+
+Copyright (c) 2024 Dave Attwood
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in
+all copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+THE SOFTWARE.
+
+ */
+
+//!
+//!   A Switch Panel Emulator in the terminal, for developing the driver without the hardware.
+//!
+//!   The 13 switches, the Magneto switch, the gear lever and the three gear LEDs are drawn
+//!   in the terminal, and operated from the keyboard. Every change sends the same 3-byte
+//!   report as the real panel to the driver, which connects with:
+//!       saitekswitch --input emulator
+//!   The driver sends the LED settings back, and these are shown as on the panel.
+//!
+//!   An optional argument gives a different address to accept the driver on.
+//!
+//!   With '--pipe' the reports are written to STDOUT instead, for the driver to read
+//!   from STDIN, and the panel is drawn through STDERR:
+//!       saitekswitch-emulator --pipe | saitekswitch --input pipe
+//!   A pipe has no way back, so the driver shows the LED settings in its own output.
+//!
+
+use switch::{GearLeds, GearPosition, LedColour, MagnetoPosition, SwitchId, EMULATOR_ADDRESS};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+
+use std::env;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+// How long to wait for a key before checking the driver connection
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Longest wait for the driver to take a report before it is taken to have gone
+const SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Keys operating each switch, in panel order
+const SWITCH_KEYS: [char; 13] = [
+    'b', 'a', 'v', 'f', 'd', 'p', 'c', 'i', 'e', 'n', 's', 't', 'l',
+];
+
+/// The settings of the emulated panel controls, and its LEDs
+struct Panel {
+    switches: [bool; 13],
+    magneto: usize, // index into MagnetoPosition::ALL
+    gear: GearPosition,
+    leds: GearLeds,
+}
+
+impl Panel {
+    fn new() -> Panel {
+        Panel {
+            switches: [false; 13],
+            magneto: 0,
+            gear: GearPosition::Down,
+            leds: GearLeds::OFF,
+        }
+    }
+
+    /// Apply a key press. Returns true if a control changed.
+    fn operate(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char(key) => {
+                if let Some(index) = SWITCH_KEYS.iter().position(|k| *k == key) {
+                    self.switches[index] = !self.switches[index];
+                    true
+                } else if key == 'g' {
+                    self.gear = match self.gear {
+                        GearPosition::Up => GearPosition::Down,
+                        GearPosition::Down => GearPosition::Up,
+                    };
+                    true
+                } else {
+                    false
+                }
+            }
+            KeyCode::Left if self.magneto > 0 => {
+                self.magneto -= 1;
+                true
+            }
+            KeyCode::Right if self.magneto < MagnetoPosition::ALL.len() - 1 => {
+                self.magneto += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The report for the current settings, as sent by the real panel
+    fn report(&self) -> [u8; 4] {
+        let mut report = MagnetoPosition::ALL[self.magneto].bit() | self.gear.bit();
+        for (id, on) in SwitchId::ALL.iter().zip(self.switches.iter()) {
+            if *on {
+                report |= id.bit();
+            }
+        }
+        [(report >> 16) as u8, (report >> 8) as u8, report as u8, 0]
+    }
+}
+
+/// The connection to the driver, over a socket or a pipe
+struct Driver {
+    listener: Option<TcpListener>, // None when piped
+    stream: Option<TcpStream>,
+    pipe: Option<io::Stdout>, // while the driver reads the pipe
+    pending: Vec<u8>,         // bytes received, not yet a complete LED report
+}
+
+impl Driver {
+    fn listen(address: &str) -> io::Result<Driver> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Driver {
+            listener: Some(listener),
+            stream: None,
+            pipe: None,
+            pending: Vec::new(),
+        })
+    }
+
+    fn pipe() -> Driver {
+        Driver {
+            listener: None,
+            stream: None,
+            pipe: Some(io::stdout()),
+            pending: Vec::new(),
+        }
+    }
+
+    fn connected(&self) -> bool {
+        self.stream.is_some() || self.pipe.is_some()
+    }
+
+    /// Accept a driver if none is connected. Returns true if one was accepted.
+    fn accept(&mut self) -> bool {
+        let listener = match &self.listener {
+            Some(listener) if self.stream.is_none() => listener,
+            _ => return false,
+        };
+        match listener.accept() {
+            Ok((stream, _)) if stream.set_nonblocking(true).is_ok() => {
+                self.stream = Some(stream);
+                self.pending.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Send a report to the driver, if connected.
+    /// The socket is only read without blocking: a report is written with blocking,
+    /// so that it is not lost while the driver is busy.
+    fn send(&mut self, report: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            let sent = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(SEND_TIMEOUT)))
+                .and_then(|_| stream.write_all(report))
+                .and_then(|_| stream.set_nonblocking(true));
+            if sent.is_err() {
+                self.stream = None;
+            }
+        }
+        if let Some(pipe) = &mut self.pipe {
+            if pipe.write_all(report).and_then(|_| pipe.flush()).is_err() {
+                self.pipe = None;
+            }
+        }
+    }
+
+    /// The latest LED setting sent by the driver, if any.
+    /// Notices when the driver goes away.
+    fn leds(&mut self) -> Option<GearLeds> {
+        let stream = self.stream.as_mut()?;
+        let mut chunk = [0u8; 64];
+        match stream.read(&mut chunk) {
+            Ok(0) => self.stream = None,
+            Ok(length) => self.pending.extend_from_slice(&chunk[..length]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(_) => self.stream = None,
+        }
+        let mut leds = None;
+        while self.pending.len() >= 2 {
+            leds = Some(GearLeds::decode(self.pending[1]));
+            self.pending.drain(..2);
+        }
+        leds
+    }
+}
+
+/// The terminal the panel is drawn on: STDOUT, or STDERR when STDOUT is piped to the driver
+fn screen(piped: bool) -> Box<dyn Write> {
+    if piped {
+        Box::new(BufWriter::new(io::stderr()))
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Restores the terminal when the emulator finishes
+struct TerminalGuard {
+    piped: bool,
+}
+
+impl TerminalGuard {
+    fn enter(piped: bool) -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(screen(piped), EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard { piped })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(screen(self.piped), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn main() -> io::Result<()> {
+    let argument = env::args().nth(1);
+    let piped = argument.as_deref() == Some("--pipe");
+    let (mut driver, address) = if piped {
+        (Driver::pipe(), "STDOUT".to_string())
+    } else {
+        let address = argument.unwrap_or_else(|| EMULATOR_ADDRESS.to_string());
+        let driver = Driver::listen(&address).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Unable to accept the driver on {}: {}", address, e),
+            )
+        })?;
+        (driver, address)
+    };
+    let mut panel = Panel::new();
+
+    let _guard = TerminalGuard::enter(piped)?;
+    draw(&panel, &driver, &address)?;
+    loop {
+        let mut redraw = driver.accept();
+        if let Some(leds) = driver.leds() {
+            panel.leds = leds;
+            redraw = true;
+        }
+        let connected = driver.connected();
+        if event::poll(POLL_INTERVAL)? {
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = event::read()?
+            {
+                if code == KeyCode::Char('q') || code == KeyCode::Esc {
+                    return Ok(());
+                }
+                if panel.operate(code) {
+                    driver.send(&panel.report());
+                    redraw = true;
+                }
+            }
+        }
+        if redraw || connected != driver.connected() {
+            draw(&panel, &driver, &address)?;
+        }
+    }
+}
+
+/// Draw the panel
+fn draw(panel: &Panel, driver: &Driver, address: &str) -> io::Result<()> {
+    let mut out = screen(driver.listener.is_none());
+    queue!(out, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    let status = match (driver.connected(), &driver.listener) {
+        (true, _) => "driver connected",
+        (false, Some(_)) => "waiting for driver",
+        (false, None) => "driver has gone",
+    };
+    line(
+        &mut out,
+        0,
+        &format!("Saitek Switch Panel Emulator - {} on {}", status, address),
+    )?;
+
+    for (row, (id, on)) in SwitchId::ALL.iter().zip(panel.switches.iter()).enumerate() {
+        line(
+            &mut out,
+            row as u16 + 2,
+            &format!(
+                "  [{}] {:<12}{}",
+                SWITCH_KEYS[row],
+                id.name(),
+                if *on { "ON" } else { "off" }
+            ),
+        )?;
+    }
+
+    let mut magneto = String::from("  [<- ->] MAGNETO    ");
+    for (index, position) in MagnetoPosition::ALL.iter().enumerate() {
+        if index == panel.magneto {
+            magneto.push_str(&format!("[{}] ", position.name()));
+        } else {
+            magneto.push_str(&format!(" {}  ", position.name()));
+        }
+    }
+    line(&mut out, 16, &magneto)?;
+    line(
+        &mut out,
+        17,
+        &format!("  [g] GEAR LEVER      {:?}", panel.gear),
+    )?;
+
+    queue!(out, cursor::MoveTo(0, 19), Print("  GEAR LEDS   "))?;
+    for (name, colour) in [
+        ("nose", panel.leds.nose),
+        ("left", panel.leds.left),
+        ("right", panel.leds.right),
+    ] {
+        let colour = match colour {
+            LedColour::Off => Color::DarkGrey,
+            LedColour::Green => Color::Green,
+            LedColour::Red => Color::Red,
+            LedColour::Yellow => Color::Yellow,
+        };
+        queue!(
+            out,
+            Print(format!("{} ", name)),
+            SetForegroundColor(colour),
+            Print("\u{25cf}   "),
+            ResetColor
+        )?;
+    }
+
+    line(&mut out, 21, "  [q] quit")?;
+    out.flush()
+}
+
+/// Print a line of text at a row
+fn line(out: &mut impl Write, row: u16, text: &str) -> io::Result<()> {
+    queue!(out, cursor::MoveTo(0, row), Print(text))
+}
@@ -1,4 +1,3 @@
-
 /*
 Driver to connect a Saitek Switch Panel to Flightgear flight simulator
 
@@ -31,51 +30,608 @@ THE SOFTWARE.
 //!   The code runs (without any changes) on Linux and Windows.
 //!
 //!   To aid testing without a switch panel, a Switch Panel Emulator (not as a HID device!)
-//!   provides input in an identical manner to the real Switch Panel
-//!   This requires compiling with:
-//!       "cargo rustc -- --cfg piped"
-//!    and there are occasional "#[cfg(piped)]" or "#[cfg(not(piped))]" for conditional compilation
-//!    in the source code in the 'switch' modules.
+//!   provides input in an identical manner to the real Switch Panel.
+//!   The input source is selected at startup with the '--input' option:
+//!       --input hid            the Switch Panel (default)
+//!       --input emulator       the Switch Panel Emulator ('saitekswitch-emulator')
+//!       --input socket:<addr>  the Switch Panel Emulator at another address
+//!       --input pipe           a Switch Panel Emulator piped into STDIN
+//!       --input file:<path>    a file of captured reports
+//!       --replay <path>        a recording made with '--record <path>', with the original
+//!                              timing, or as fast as possible with '--fast'
+//!
+//!   '--debounce <milliseconds>' sets the time for which the panel must be still before
+//!   a change is sent to the simulator, to ignore contact bounce.
+//!
+//!   When several Switch Panels are attached, one is chosen with '--serial <serial number>'
+//!   or '--hid-path <path>' (or a <panel> element in the configuration file);
+//!   '--list-panels' shows the attached panels.
+//!
+//!   Without a configuration file on the command line, the file is $SAITEKSWITCH_CONFIG,
+//!   or 'switchdefaultconfig.xml' in $XDG_CONFIG_HOME/saitekswitch/ or /etc/saitekswitch/,
+//!   or else the one built into the program (see 'simulator::find_config'). The file
+//!   chosen, and where it was found, is shown at startup.
+//!
+//!   '--profiles <directory>' chooses the configuration file (profile) from the directory
+//!   for the aircraft in the simulator, and changes it when the aircraft changes.
+//!   The configuration file given is used until the simulator sends the aircraft.
+//!   '--aircraft-port <port>' is the UDP port the aircraft is sent to, 60005 by default,
+//!   so that a driver for each of several panels can choose profiles.
+//!
+//!   The configuration file is loaded again when it is changed, or on SIGHUP, keeping
+//!   the configuration loaded before if the file has mistakes.
+//!
+//!   'saitekswitch check [configuration file]' checks a configuration file without the
+//!   Switch Panel or the simulator, showing the property each control is sent to and
+//!   any mistakes in the file.
+//!
+//!   'saitekswitch convert [configuration file] [output file]' writes the configuration
+//!   as a TOML or JSON file, by the output file extension, '.toml' if none is given.
+//!   The files the configuration extends or includes are merged in. An existing file is
+//!   not overwritten.
 //!
 
-use simulator::Simulator;
+use simulator::{
+    find_config, find_profile, profile_files, spawn_aircraft_listener, spawn_listener, ConfigError,
+    Format, GearListener, Simulator, SwitchCommand, SwitchMode, AIRCRAFT_FEEDBACK_PORT,
+    BUILTIN_PREFIX,
+};
 use std::env;
-use switch::Device;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use switch::{
+    Device, GearLeds, GearPosition, HidInput, InputKind, MagnetoPosition, PanelSelector,
+    ReadStatus, SwitchId,
+};
+
+// Longest wait for the panel before the run loop does its periodic work
+const TICK: Duration = Duration::from_millis(100);
+// How often the configuration files are checked for changes
+const RELOAD_CHECK: Duration = Duration::from_secs(1);
+
+const USAGE: &str =
+    "Usage: saitekswitch check [configuration file]
+       saitekswitch convert [configuration file] [output file .toml|.json]
+       saitekswitch [--input hid|emulator|socket:<address>|pipe|file:<path>|replay:<path>|replay-fast:<path>] \
+[--replay <path> [--fast]] [--record <path>] [--debounce <milliseconds>] \
+[--serial <serial number> | --hid-path <path>] [--list-panels] [--profiles <directory> [--aircraft-port <port>]] [configuration file]";
 
 fn main() -> std::io::Result<()> {
     let mut args = env::args();
     args.next();
-    // Configuration file path
-    let config = match args.next() {
-        Some(arg) => arg.to_string(),
-        None => "switchdefaultconfig.xml".to_string(),
-    };
+    // Input source
+    let mut input = InputKind::Hid(PanelSelector::First);
+    let mut panel = PanelSelector::First;
+    let mut record = None;
+    let mut replay = None;
+    let mut fast = false;
+    let mut debounce = Duration::ZERO;
+    let mut check = false;
+    let mut convert = false;
+    let mut files = Vec::new();
+    let mut profiles = None;
+    let mut aircraft_port = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                input = args
+                    .next()
+                    .unwrap_or_default()
+                    .parse()
+                    .unwrap_or_else(|e: String| usage_exit(&e));
+            }
+            "--serial" => {
+                panel = PanelSelector::Serial(
+                    args.next()
+                        .unwrap_or_else(|| usage_exit("--serial needs a serial number")),
+                );
+            }
+            "--hid-path" => {
+                panel = PanelSelector::Path(
+                    args.next()
+                        .unwrap_or_else(|| usage_exit("--hid-path needs a path")),
+                );
+            }
+            "--record" => {
+                record = Some(
+                    args.next()
+                        .unwrap_or_else(|| usage_exit("--record needs a file")),
+                );
+            }
+            "--replay" => {
+                replay = Some(
+                    args.next()
+                        .unwrap_or_else(|| usage_exit("--replay needs a file")),
+                );
+            }
+            "--fast" => fast = true,
+            "--debounce" => {
+                let millis = args
+                    .next()
+                    .and_then(|millis| millis.parse().ok())
+                    .unwrap_or_else(|| usage_exit("--debounce needs a time in milliseconds"));
+                debounce = Duration::from_millis(millis);
+            }
+            "--profiles" => {
+                profiles =
+                    Some(PathBuf::from(args.next().unwrap_or_else(|| {
+                        usage_exit("--profiles needs a directory")
+                    })));
+            }
+            "--aircraft-port" => {
+                aircraft_port = Some(
+                    args.next()
+                        .and_then(|port| port.parse().ok())
+                        .unwrap_or_else(|| usage_exit("--aircraft-port needs a port number")),
+                );
+            }
+            "--list-panels" => {
+                list_panels().unwrap_or_else(|e| error_exit(&e));
+                return Ok(());
+            }
+            "check" => check = true,
+            "convert" => convert = true,
+            _ if arg.starts_with("--") => usage_exit(&format!("Unknown option '{}'", arg)),
+            _ => files.push(arg),
+        }
+    }
+    let mut files = files.into_iter();
+    let (config, found) = find_config(files.next());
+    let output = files.next();
+    if (output.is_some() && !convert) || files.next().is_some() {
+        usage_exit("Too many files given");
+    }
+    if convert || check {
+        println!("Configuration file {} ({})", config, found);
+    }
+    if convert {
+        convert_config(&config, output);
+        return Ok(());
+    }
+    if check {
+        check_config(&config);
+        return Ok(());
+    }
+    if fast && replay.is_none() {
+        usage_exit("--fast can only be used with --replay");
+    }
+    if aircraft_port.is_some() && profiles.is_none() {
+        usage_exit("--aircraft-port can only be used with --profiles");
+    }
+    if panel != PanelSelector::First {
+        // the panel is chosen only for the Switch Panel, not another input source
+        match input {
+            InputKind::Hid(_) if replay.is_none() => input = InputKind::Hid(panel),
+            _ => usage_exit("--serial and --hid-path can only be used with --input hid"),
+        }
+    }
+    if let Some(path) = replay {
+        input = InputKind::Replay { path, fast };
+    }
 
     println!(
-        "{} Version {} Configuration file {}",
+        "{} Version {} Configuration file {} ({})",
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION"),
-        config
+        config,
+        found
+    );
+    let profiles =
+        profiles.map(|directory| (directory, aircraft_port.unwrap_or(AIRCRAFT_FEEDBACK_PORT)));
+    run(config, input, record, debounce, profiles).unwrap_or_else(|e| error_exit(&e));
+    Ok(())
+}
+
+/// Write a configuration file as TOML or JSON, refusing to replace an existing file
+fn convert_config(config: &str, output: Option<String>) {
+    // a built in file is written to the current directory
+    let output = output.map_or_else(
+        || Path::new(config.trim_start_matches(BUILTIN_PREFIX)).with_extension("toml"),
+        PathBuf::from,
+    );
+    let format = Format::of(&output);
+    if format == Format::Xml {
+        usage_exit("convert writes a .toml or .json file");
+    }
+    if output.exists() {
+        println!("{} already exists, not overwritten", output.display());
+        process::exit(1);
+    }
+    let simulator = Simulator::new(config).unwrap_or_else(|errors| config_exit(&errors));
+    for warning in &simulator.warnings {
+        println!("Warning {}", warning);
+    }
+    let text = simulator.convert(format).unwrap_or_else(|e| {
+        println!("Unable to convert {}: {}", config, e);
+        process::exit(1);
+    });
+    fs::write(&output, text).unwrap_or_else(|e| {
+        println!("Unable to write {}: {}", output.display(), e);
+        process::exit(1);
+    });
+    println!("Converted {} to {}", config, output.display());
+}
+
+/// Report a fatal error and exit
+fn error_exit(err: &io::Error) -> ! {
+    println!("{}", err);
+    process::exit(1);
+}
+
+/// Report the problems in the configuration file and exit
+fn config_exit(errors: &[ConfigError]) -> ! {
+    for error in errors {
+        println!("{}", error);
+    }
+    println!("{} configuration error(s)", errors.len());
+    process::exit(4);
+}
+
+/// Load a configuration file, without the Switch Panel or the simulator, and show
+/// the property each control is sent to, and any warnings
+fn check_config(config: &str) {
+    let simulator = Simulator::new(config).unwrap_or_else(|errors| config_exit(&errors));
+    if !simulator.aircraft.is_empty() {
+        println!("  {:<12}{}", "AIRCRAFT", simulator.aircraft.join(", "));
+    }
+    for id in SwitchId::ALL {
+        print_switch(id.name(), simulator.switch_mapper.get(&id));
+    }
+    for position in [GearPosition::Up, GearPosition::Down] {
+        if let Some(command) = simulator.gear_mapper.get(&position) {
+            print_switch(position.name(), Some(command));
+        }
+    }
+    if simulator.magneto.targets.is_empty() {
+        println!("  {:<12}(none)", "MAGNETO");
+    }
+    for (index, target) in simulator.magneto.targets.iter().enumerate() {
+        let mut details: Vec<String> = MagnetoPosition::ALL
+            .iter()
+            .filter_map(|position| {
+                target
+                    .value(*position)
+                    .map(|value| format!("{} {}", position.name(), value))
+            })
+            .collect();
+        if !target.delay.is_zero() {
+            details.push(format!("after {}ms", target.delay.as_millis()));
+        }
+        println!(
+            "  {:<12}{} ({})",
+            if index == 0 { "MAGNETO" } else { "" },
+            target.property,
+            details.join(", ")
+        );
+    }
+    print_switch("STARTER", Some(&simulator.starter));
+    if !simulator.gear_retarget.targets.is_empty() {
+        print_switch("GEAR LEVER", Some(&simulator.gear_retarget));
+    }
+    if let Some(primer) = &simulator.gear_primer {
+        println!(
+            "  {:<12}{} (add 1, {} stroke(s) {}ms apart)",
+            "GEAR PRIMER",
+            primer.property,
+            primer.strokes,
+            primer.interval.as_millis()
+        );
+    }
+    println!("  {:<12}{}", "PANEL", simulator.panel);
+    match &simulator.gear_leds {
+        Some(gear_leds) => println!(
+            "  {:<12}port {}, gear units nose {} left {} right {}",
+            "GEAR LEDS", gear_leds.port, gear_leds.nose, gear_leds.left, gear_leds.right
+        ),
+        None => println!("  {:<12}(none)", "GEAR LEDS"),
+    }
+    for warning in &simulator.warnings {
+        println!("Warning {}", warning);
+    }
+    println!(
+        "Configuration file {} is usable, {} warning(s)",
+        config,
+        simulator.warnings.len()
     );
-    run(config);
+}
+
+/// Print each target of a switch, one per line
+fn print_switch(name: &str, command: Option<&SwitchCommand>) {
+    let targets = command
+        .map(|command| command.targets.as_slice())
+        .unwrap_or(&[]);
+    if targets.is_empty() {
+        println!("  {:<12}(none)", name);
+    }
+    for (index, target) in targets.iter().enumerate() {
+        let mut details = match command {
+            Some(command) if command.mode == SwitchMode::Toggle => "toggle".to_string(),
+            _ => format!("on {}, off {}", target.value(true), target.value(false)),
+        };
+        if target.invert {
+            details.push_str(", inverted");
+        }
+        if !target.delay.is_zero() {
+            details.push_str(&format!(", after {}ms", target.delay.as_millis()));
+        }
+        if let Some(command) = command.filter(|command| command.mode == SwitchMode::Pulse) {
+            details.push_str(&format!(
+                ", pulse {}ms, edge {}",
+                command.duration.as_millis(),
+                command.edge.name()
+            ));
+        }
+        println!(
+            "  {:<12}{} ({})",
+            if index == 0 { name } else { "" },
+            target.property,
+            details
+        );
+    }
+}
+
+/// Print the serial number and HID path of each attached Switch Panel
+fn list_panels() -> io::Result<()> {
+    let panels = HidInput::list()?;
+    if panels.is_empty() {
+        println!("No Saitek Switch found");
+    }
+    for panel in panels {
+        println!(
+            "Saitek Switch serial number {} path {}",
+            panel.serial.as_deref().unwrap_or("(none)"),
+            panel.path
+        );
+    }
     Ok(())
 }
 
-/// Indefinite loop Driver for the switch panel to simulator interfaced
-fn run(config: String) {
-    let mut my_device = Device::new(); // access the device
-    let mut my_simulator = Simulator::new(&config); // map device to simulator
-    my_simulator.initialise_switches(my_device.get_current_input()); // initial switch settings provided from Device::new
-    my_device.preserve_current_input();
+/// Report a command line problem and exit
+fn usage_exit(message: &str) -> ! {
+    println!("{}", message);
+    println!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Indefinite loop Driver for the switch panel to simulator interfaced.
+/// Returns when the input source has no more data.
+fn run(
+    mut config: String,
+    input: InputKind,
+    record: Option<String>,
+    debounce: Duration,
+    profiles: Option<(PathBuf, u16)>, // the directory, and the port the aircraft is sent to
+) -> io::Result<()> {
+    // map device to simulator
+    let mut my_simulator = Simulator::new(&config).unwrap_or_else(|errors| config_exit(&errors));
+    for warning in &my_simulator.warnings {
+        println!("Warning {}", warning);
+    }
+    // a panel chosen on the command line takes precedence over the configuration file
+    let input = match input {
+        InputKind::Hid(PanelSelector::First) => InputKind::Hid(my_simulator.panel.clone()),
+        _ => input,
+    };
+    println!("Input {}", input);
+    // access the device
+    let mut my_device = Device::new(input.open()?)?;
+    my_device.set_debounce(debounce);
+    if let Some(path) = record {
+        my_device.start_recording(&path)?;
+        println!("Recording to {}", path);
+    }
+    // gear positions from the simulator, shown on the gear LEDs
+    let mut gear_listener = None;
+    start_gear_leds(&my_simulator, &my_device, &mut gear_listener)?;
+    // the aircraft from the simulator, choosing the profile
+    let aircraft = match &profiles {
+        Some((directory, port)) => {
+            println!(
+                "Aircraft from simulator port {}, profiles in {}",
+                port,
+                directory.display()
+            );
+            Some(spawn_aircraft_listener(*port)?)
+        }
+        None => None,
+    };
+    // the configuration file is reloaded when it is changed, or on SIGHUP
+    let hangup = reload_signal()?;
+    let mut modified = modified_times(&my_simulator.files);
+    let mut checked = Instant::now();
+    // the aircraft whose profile could not be loaded, and the profiles when it was tried
+    let mut unloaded: Option<(String, Vec<Option<SystemTime>>)> = None;
+    // initial switch settings provided from Device::new
+    my_simulator.initialise_switches(&my_device.state_events());
     loop {
-        my_device.read(); // blocking read
-                          // println!("Read: 0x{:06x}", my_device.input_current);
-        if my_device.has_input_changed() {
-            my_simulator.process_input(
-                my_device.get_current_input(),
-                my_device.get_previous_input(),
+        // wait up to one tick for the panel, or until the next write to the simulator is due
+        let wait = my_simulator.until_due().map_or(TICK, |due| due.min(TICK));
+        match my_device.read_timeout(wait) {
+            Ok(None) => {} // nothing from the panel this tick
+            Ok(Some(ReadStatus::Report(events))) => {
+                for event in &events {
+                    my_simulator.process_event(event);
+                }
+            }
+            // the panel has been plugged back in, resend all the switch settings
+            Ok(Some(ReadStatus::Reconnected(state))) => my_simulator.initialise_switches(&state),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                println!("Saitek Switch input ended");
+                my_simulator.finish(); // end any pulses
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+        my_simulator.tick();
+        let check_files = checked.elapsed() >= RELOAD_CHECK;
+        if check_files {
+            checked = Instant::now();
+        }
+        // the simulator has changed aircraft, or the profiles have changed since
+        // the profile for the aircraft could not be loaded
+        if let Some((directory, _)) = &profiles {
+            let mut name = aircraft
+                .as_ref()
+                .and_then(|aircraft| aircraft.try_recv().ok());
+            if let (None, Some((failed, tried)), true) = (&name, &unloaded, check_files) {
+                if profile_times(directory) != *tried {
+                    name = Some(failed.clone());
+                }
+            }
+            if let Some(name) = name {
+                let tried = profile_times(directory);
+                unloaded = None;
+                match load_profile(directory, &name, &config) {
+                    Profile::Loaded(profile, simulator) => {
+                        my_simulator.finish();
+                        my_simulator = *simulator;
+                        config = profile;
+                        start_gear_leds(&my_simulator, &my_device, &mut gear_listener)?;
+                        my_simulator.initialise_switches(&my_device.state_events());
+                        modified = modified_times(&my_simulator.files);
+                    }
+                    Profile::Current => {}
+                    Profile::Failed => unloaded = Some((name, tried)),
+                }
+            }
+        }
+        // the configuration file has been edited
+        let mut reload = hangup.swap(false, Ordering::Relaxed);
+        if check_files {
+            reload |= modified_times(&my_simulator.files) != modified;
+        }
+        if reload {
+            match Simulator::new(&config) {
+                Ok(simulator) => {
+                    println!("Configuration file {} reloaded", config);
+                    for warning in &simulator.warnings {
+                        println!("Warning {}", warning);
+                    }
+                    my_simulator.reload(simulator, &my_device.state_events());
+                    // restarted, or stopped, if <gear-leds> has changed
+                    start_gear_leds(&my_simulator, &my_device, &mut gear_listener)?;
+                }
+                Err(errors) => {
+                    for error in &errors {
+                        println!("{}", error);
+                    }
+                    println!(
+                        "{} configuration error(s), keeping the configuration loaded before",
+                        errors.len()
+                    );
+                }
+            }
+            // not tried again until the file is changed again
+            modified = modified_times(&my_simulator.files);
+        }
+    }
+}
+
+/// Show the gear positions from the simulator on the gear LEDs, as the <gear-leds> element
+/// of the configuration gives. The listener is started again when the element has changed,
+/// and stopped with the LEDs turned off when the configuration has none.
+fn start_gear_leds(
+    simulator: &Simulator,
+    device: &Device,
+    listener: &mut Option<GearListener>,
+) -> io::Result<()> {
+    if listener.as_ref().map(GearListener::config) == simulator.gear_leds.as_ref() {
+        return Ok(());
+    }
+    if listener.take().is_some() {
+        device.led_control().set(GearLeds::OFF);
+        println!("Gear LEDs stopped");
+    }
+    if let Some(gear_leds) = &simulator.gear_leds {
+        *listener = Some(spawn_listener(gear_leds.clone(), device.led_control())?);
+        println!("Gear LEDs from simulator port {}", gear_leds.port);
+    }
+    Ok(())
+}
+
+/// When each of the configuration files was last changed, None if it cannot be read
+fn modified_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+/// A flag set when the driver is sent SIGHUP, to reload the configuration file
+#[cfg(unix)]
+fn reload_signal() -> io::Result<Arc<AtomicBool>> {
+    let flag = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&flag))?;
+    Ok(flag)
+}
+
+/// There is no SIGHUP on Windows, the configuration file is reloaded when it is changed
+#[cfg(not(unix))]
+fn reload_signal() -> io::Result<Arc<AtomicBool>> {
+    Ok(Arc::new(AtomicBool::new(false)))
+}
+
+/// The modification times of the profiles in 'directory', to notice when they change
+fn profile_times(directory: &Path) -> Vec<Option<SystemTime>> {
+    modified_times(&profile_files(directory).unwrap_or_default())
+}
+
+/// The outcome of looking for the profile for an aircraft
+enum Profile {
+    Loaded(String, Box<Simulator>), // the profile file, and its configuration
+    Current,                        // the configuration file already in use
+    Failed,                         // none, or it has errors: tried again when the profiles change
+}
+
+/// Load the profile in 'directory' for the aircraft, unless it is the configuration
+/// file already in use. The current configuration is kept if there is no profile
+/// for the aircraft or it has errors.
+fn load_profile(directory: &Path, aircraft: &str, current: &str) -> Profile {
+    let profile = match find_profile(directory, aircraft) {
+        Ok(Some(profile)) => profile.display().to_string(),
+        Ok(None) => {
+            println!(
+                "Aircraft {}, no profile for it, keeping configuration file {}",
+                aircraft, current
+            );
+            return Profile::Failed;
+        }
+        Err(e) => {
+            println!("{}", e);
+            return Profile::Failed;
+        }
+    };
+    if profile == current {
+        return Profile::Current;
+    }
+    match Simulator::new(&profile) {
+        Ok(simulator) => {
+            println!("Aircraft {}, configuration file {}", aircraft, profile);
+            for warning in &simulator.warnings {
+                println!("Warning {}", warning);
+            }
+            Profile::Loaded(profile, Box::new(simulator))
+        }
+        Err(errors) => {
+            for error in &errors {
+                println!("{}", error);
+            }
+            println!(
+                "Aircraft {}, {} configuration error(s), keeping configuration file {}",
+                aircraft,
+                errors.len(),
+                current
             );
+            Profile::Failed
         }
-        my_device.preserve_current_input();
     }
 }
@@ -0,0 +1,108 @@
+//!
+//! Choosing the configuration file (profile) for the aircraft in the simulator.
+//!
+//! FGFS sends '/sim/aircraft' using the 'saitekswitch-aircraft' generic protocol,
+//! one line per update:
+//!     c172p
+//! The profile is the configuration file in the profile directory whose <plane>
+//! element lists the aircraft:
+//!     <plane aircraft="c172p c172p-ifr">Cessna 172P</plane>
+//! or, in a TOML or JSON file, whose 'aircraft' setting does. The built in
+//! profiles are looked at last.
+//!
+
+use crate::config::plane_aircraft;
+use crate::gear_feedback::RECEIVE_ERROR_WAIT;
+use crate::profiles::builtin_profiles;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const AIRCRAFT_FEEDBACK_ADDRESS: &str = "127.0.0.1";
+pub const AIRCRAFT_FEEDBACK_PORT: u16 = 60005; // by default, don't clash with the gear positions
+
+/// Start a thread listening for the aircraft from the simulator.
+/// The aircraft is received when it is first sent, and each time it changes;
+/// the driver tries again itself if the profile could not be loaded.
+pub fn spawn_aircraft_listener(port: u16) -> io::Result<Receiver<String>> {
+    let socket = UdpSocket::bind((AIRCRAFT_FEEDBACK_ADDRESS, port)).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "Unable to receive the aircraft on port {} (choose another with --aircraft-port): {}",
+                port, e
+            ),
+        )
+    })?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        let mut last = String::new();
+        loop {
+            let length = match socket.recv(&mut buf) {
+                Ok(length) => length,
+                Err(e) => {
+                    println!("Aircraft receive error: {}", e);
+                    thread::sleep(RECEIVE_ERROR_WAIT);
+                    continue;
+                }
+            };
+            // only the latest complete line matters
+            let data = String::from_utf8_lossy(&buf[..length]);
+            if let Some(line) = data
+                .lines()
+                .rev()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+            {
+                if line != last {
+                    last = line.to_string();
+                    if sender.send(last.clone()).is_err() {
+                        return; // nobody is listening
+                    }
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// The configuration file in 'directory' for the aircraft, or else the built in one,
+/// None if there is none. Files which cannot be read are passed over; if several list
+/// the aircraft, the first by name is used.
+pub fn find_profile(directory: &Path, aircraft: &str) -> io::Result<Option<PathBuf>> {
+    let mut files = profile_files(directory)?;
+    files.extend(builtin_profiles());
+    Ok(files
+        .into_iter()
+        .find(|path| plane_aircraft(path).iter().any(|name| name == aircraft)))
+}
+
+/// The configuration files in the profile directory, in name order
+pub fn profile_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(directory)
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "Unable to read profile directory '{}': {}",
+                    directory.display(),
+                    e
+                ),
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            ["xml", "toml", "json"]
+                .iter()
+                .any(|extension| path.extension() == Some(OsStr::new(extension)))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,326 @@
+//!
+//! Values sent to simulator properties, and the command for each switch.
+//! A switch may set several properties (its targets), e.g. a master switch
+//! setting both the battery and a bus relay, and the Magneto switch may set
+//! the magnetos of each engine of a twin.
+//!
+//! A value is sent to FGFS as its text and its type, which the Nasal code in
+//! 'saitekswitch.nas' uses to convert it before setting the property:
+//!     controls/lighting/instruments-norm,double,0.8
+//!
+//! A switch may instead pulse its targets, for controls which are push buttons in
+//! the simulator: the 'on' values are sent when the switch is operated, and the
+//! 'off' values once the pulse is over. For aircraft where a property can only
+//! be toggled, a switch may send a toggle command each time it is operated.
+//!
+
+use switch::MagnetoPosition;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Default time between the strokes of the primer
+const PRIMER_STROKE_INTERVAL: Duration = Duration::from_millis(500);
+// Default time a pulse is held on
+const PULSE_DURATION: Duration = Duration::from_millis(200);
+
+/// A value for a simulator property, written in TOML or JSON as the value itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    /// The type name sent with the value, as understood by 'saitekswitch.nas'
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Double(_) => "double",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+        }
+    }
+
+    /// Text values may not contain the ',' or newline used by the protocol
+    pub(crate) fn check(&self) -> Result<(), String> {
+        match self {
+            Value::String(text) if text.contains(',') || text.contains('\n') => {
+                Err("a value may not contain ',' or a newline".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Double(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = String;
+
+    /// Parse 'true' or 'false', an integer, a number, or any other text as a string.
+    /// The text may not contain the ',' or newline used by the protocol.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = match s {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match (s.parse::<i64>(), s.parse::<f64>()) {
+                (Ok(value), _) => Value::Int(value),
+                (_, Ok(value)) if value.is_finite() => Value::Double(value),
+                _ => Value::String(s.to_string()),
+            },
+        };
+        value.check().map(|_| value)
+    }
+}
+
+/// A simulator property set by a switch, the values sent when the switch is turned
+/// on and off, and the time to wait before sending
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub property: String,
+    pub on: Value,
+    pub off: Value,
+    pub invert: bool, // send the 'off' value when the switch is on, and the 'on' value when off
+    pub delay: Duration, // after the previous target
+}
+
+impl Target {
+    /// A target sending 1 (on) and 0 (off) to 'property' without delay
+    pub fn new(property: &str) -> Target {
+        Target {
+            property: property.to_string(),
+            on: Value::Int(1),
+            off: Value::Int(0),
+            invert: false,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// The value to send for the switch setting
+    pub fn value(&self, on: bool) -> &Value {
+        if on != self.invert {
+            &self.on
+        } else {
+            &self.off
+        }
+    }
+}
+
+/// How a switch operates its targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchMode {
+    #[default]
+    Level, // send the value for the switch setting
+    Pulse,  // send the 'on' values, then the 'off' values after the pulse duration
+    Toggle, // toggle the property in the simulator
+}
+
+impl SwitchMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SwitchMode::Level => "level",
+            SwitchMode::Pulse => "pulse",
+            SwitchMode::Toggle => "toggle",
+        }
+    }
+}
+
+impl FromStr for SwitchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "level" => Ok(SwitchMode::Level),
+            "pulse" => Ok(SwitchMode::Pulse),
+            "toggle" => Ok(SwitchMode::Toggle),
+            _ => Err("expected 'level', 'pulse' or 'toggle'".to_string()),
+        }
+    }
+}
+
+/// Which movements of a switch fire a pulse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PulseEdge {
+    #[default]
+    On, // turning the switch on
+    Off,  // turning the switch off
+    Both, // either
+}
+
+impl PulseEdge {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PulseEdge::On => "on",
+            PulseEdge::Off => "off",
+            PulseEdge::Both => "both",
+        }
+    }
+
+    /// Whether turning the switch to 'on' fires a pulse
+    pub fn fires(&self, on: bool) -> bool {
+        match self {
+            PulseEdge::On => on,
+            PulseEdge::Off => !on,
+            PulseEdge::Both => true,
+        }
+    }
+}
+
+impl FromStr for PulseEdge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on" => Ok(PulseEdge::On),
+            "off" => Ok(PulseEdge::Off),
+            "both" => Ok(PulseEdge::Both),
+            _ => Err("expected 'on', 'off' or 'both'".to_string()),
+        }
+    }
+}
+
+/// The properties set by a switch, in the order they are sent, and how they are set
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCommand {
+    pub targets: Vec<Target>,
+    pub mode: SwitchMode,
+    pub duration: Duration, // of a pulse
+    pub edge: PulseEdge,    // firing a pulse
+}
+
+impl Default for SwitchCommand {
+    fn default() -> Self {
+        SwitchCommand {
+            targets: Vec::new(),
+            mode: SwitchMode::Level,
+            duration: PULSE_DURATION,
+            edge: PulseEdge::On,
+        }
+    }
+}
+
+impl SwitchCommand {
+    /// A command setting the one property
+    pub fn new(property: &str) -> SwitchCommand {
+        SwitchCommand {
+            targets: vec![Target::new(property)],
+            ..SwitchCommand::default()
+        }
+    }
+}
+
+/// A simulator property set by the Magneto switch, the value sent for each position,
+/// and the time to wait before sending
+#[derive(Debug, Clone, PartialEq)]
+pub struct MagnetoTarget {
+    pub property: String,
+    pub values: HashMap<MagnetoPosition, Value>,
+    pub delay: Duration, // after the previous target
+}
+
+impl MagnetoTarget {
+    /// A target sending OFF 0, R 1, L 2, BOTH 3 and START 4 to 'property' without delay
+    pub fn new(property: &str) -> MagnetoTarget {
+        MagnetoTarget {
+            property: property.to_string(),
+            values: MagnetoPosition::ALL
+                .iter()
+                .zip(0..)
+                .map(|(position, value)| (*position, Value::Int(value)))
+                .collect(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// The value to send for the position, None if nothing is sent
+    pub fn value(&self, position: MagnetoPosition) -> Option<&Value> {
+        self.values.get(&position)
+    }
+}
+
+/// The properties set by the Magneto switch, in the order they are sent
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MagnetoCommand {
+    pub targets: Vec<MagnetoTarget>,
+}
+
+/// The gear lever used as a primer pump: each time the lever is moved down, one
+/// is added to the property for each stroke
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimerCommand {
+    pub property: String,
+    pub strokes: u32,
+    pub interval: Duration, // between strokes
+}
+
+impl PrimerCommand {
+    /// A primer giving one stroke each time the lever is moved down
+    pub fn new(property: &str) -> PrimerCommand {
+        PrimerCommand {
+            property: property.to_string(),
+            strokes: 1,
+            interval: PRIMER_STROKE_INTERVAL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_values() {
+        let value = |s: &str| s.parse::<Value>();
+        assert_eq!(value("true"), Ok(Value::Bool(true)));
+        assert_eq!(value("false"), Ok(Value::Bool(false)));
+        assert_eq!(value("1"), Ok(Value::Int(1)));
+        assert_eq!(value("-20"), Ok(Value::Int(-20)));
+        assert_eq!(value("0.8"), Ok(Value::Double(0.8)));
+        assert_eq!(value("1e3"), Ok(Value::Double(1000.0)));
+        assert_eq!(value("TRUE"), Ok(Value::String("TRUE".to_string())));
+        assert_eq!(value("inf"), Ok(Value::String("inf".to_string())));
+        assert_eq!(value(""), Ok(Value::String(String::new())));
+        assert!(value("1,2").is_err());
+        assert!(value("a\nb").is_err());
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(Value::Int(0).type_name(), "int");
+        assert_eq!(Value::Double(0.5).type_name(), "double");
+        assert_eq!(Value::Bool(true).type_name(), "bool");
+        assert_eq!(Value::String("on".to_string()).type_name(), "string");
+    }
+
+    #[test]
+    fn target_values() {
+        let target = Target::new("a/b");
+        assert_eq!(target.value(true), &Value::Int(1));
+        assert_eq!(target.value(false), &Value::Int(0));
+        let inverted = Target {
+            invert: true,
+            ..target
+        };
+        assert_eq!(inverted.value(true), &Value::Int(0));
+        assert_eq!(inverted.value(false), &Value::Int(1));
+    }
+}
@@ -0,0 +1,1045 @@
+//!
+//! Loading the XML configuration file into the 'Simulator' mapping tables.
+//!
+//! Every problem found in the file is collected as a 'ConfigError', giving the
+//! file, the line and column of the element at fault and what is wrong, so that
+//! all of them can be put right at once. Mistakes which do not stop the
+//! configuration being used, such as a switch configured twice, are collected
+//! as warnings in 'Simulator::warnings'.
+//!
+
+use crate::model::{load_model, model_aircraft, Format};
+use crate::profiles::builtin_profile;
+use crate::{
+    GearLedConfig, MagnetoCommand, MagnetoTarget, PrimerCommand, Simulator, SwitchCommand,
+    SwitchMode, Target,
+};
+
+use switch::{GearPosition, MagnetoPosition, PanelSelector, SwitchId};
+
+use xml::common::{Position, TextPosition};
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A problem in a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub file: String,
+    pub position: Option<(u64, u64)>, // line and column, counting from 1, None for the whole file
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => {
+                write!(f, "{}:{}:{}: {}", self.file, line, column, self.message)
+            }
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// Collects the problems found while loading one file
+pub(crate) struct Problems {
+    pub(crate) file: String,
+    pub(crate) errors: Vec<ConfigError>,
+    pub(crate) warnings: Vec<ConfigError>,
+}
+
+impl Problems {
+    fn error(&self, position: Option<TextPosition>, message: String) -> ConfigError {
+        ConfigError {
+            file: self.file.clone(),
+            position: position.map(|position| (position.row + 1, position.column + 1)),
+            message,
+        }
+    }
+
+    /// A problem at the position of the last event read by 'parser'
+    fn at<R: std::io::Read>(&mut self, parser: &EventReader<R>, message: String) {
+        let error = self.error(Some(parser.position()), message);
+        self.errors.push(error);
+    }
+
+    /// A problem with the file as a whole
+    pub(crate) fn file(&mut self, message: String) {
+        let error = self.error(None, message);
+        self.errors.push(error);
+    }
+
+    /// A problem at 'position', None for the file as a whole
+    pub(crate) fn error_at(&mut self, position: Option<TextPosition>, message: String) {
+        let error = self.error(position, message);
+        self.errors.push(error);
+    }
+
+    /// A mistake at 'position' which does not stop the configuration being used
+    pub(crate) fn warn(&mut self, position: Option<TextPosition>, message: String) {
+        let warning = self.error(position, message);
+        self.warnings.push(warning);
+    }
+}
+
+// Tag names in XML configuration file
+enum StartType {
+    Plane,
+    Switch,
+    Target,
+    Magnetos,
+    MagnetoTarget,
+    GearPrimer,
+    Panel,
+    GearLeds,
+    Unknown, // an element in error, or text outside an element, ignored
+}
+
+// Names of <switch> elements, the labelled switches or the gear lever positions,
+// and the <starter> and <gear-retarget>, which are configured as switches
+enum SwitchName {
+    Switch(SwitchId),
+    Gear(GearPosition),
+    Starter,
+    GearRetarget,
+}
+
+// Elements which contain <target>s
+enum Parent {
+    Switch(SwitchName),
+    Magnetos,
+    Unknown, // a <switch> in error
+}
+
+/// Parse the value of an attribute
+fn parse_attribute<T: FromStr>(element: &str, attribute: &str, value: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid <{}> {} {:?}: {}", element, attribute, value, e))
+}
+
+/// Parse an attribute giving the values sent to a target, for a <switch> or a <target>
+fn target_attribute(
+    element: &str,
+    attribute: &str,
+    value: &str,
+    target: &mut Target,
+) -> Result<(), String> {
+    match attribute {
+        "delay" if element == "target" => parse_attribute(element, attribute, value)
+            .map(|delay| target.delay = Duration::from_millis(delay)),
+        "on" => parse_attribute(element, attribute, value).map(|on| target.on = on),
+        "off" => parse_attribute(element, attribute, value).map(|off| target.off = off),
+        "invert" => parse_attribute(element, attribute, value).map(|invert| target.invert = invert),
+        _ => Err(format!(
+            "Unexpected <{}> attribute {:?}",
+            element, attribute
+        )),
+    }
+}
+
+/// Parse an attribute giving the value sent for a Magneto position, for <magnetos> or a <target>.
+/// The attribute is the position name in lower case.
+fn magneto_attribute(
+    element: &str,
+    attribute: &str,
+    value: &str,
+    target: &mut MagnetoTarget,
+) -> Result<(), String> {
+    if attribute == "delay" && element == "target" {
+        return parse_attribute(element, attribute, value)
+            .map(|delay| target.delay = Duration::from_millis(delay));
+    }
+    match MagnetoPosition::ALL
+        .iter()
+        .find(|position| position.name().to_lowercase() == attribute)
+    {
+        Some(position) => parse_attribute(element, attribute, value).map(|value| {
+            target.values.insert(*position, value);
+        }),
+        None => Err(format!(
+            "Unexpected <{}> attribute {:?}",
+            element, attribute
+        )),
+    }
+}
+
+/// Processes the configuration file to build the mapping tables in the simulator
+/// This is called from 'new', so there is no 'self' yet
+pub(crate) fn config_loader(
+    filename: &str,
+    config_data: &mut Simulator,
+) -> Result<(), Vec<ConfigError>> {
+    let mut files = Vec::new();
+    let mut problems = load_file(filename, config_data, &mut files);
+    check_complete(config_data, &mut problems);
+    config_data.warnings = problems.warnings;
+
+    if problems.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.errors)
+    }
+}
+
+/// Loads one configuration file into the mapping tables, and the files it extends or
+/// includes, returning the problems found. 'files' are the files being loaded, each
+/// extended or included by the one before, to find cycles.
+fn load_file(filename: &str, config_data: &mut Simulator, files: &mut Vec<PathBuf>) -> Problems {
+    let mut problems = Problems {
+        file: filename.to_string(),
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let file = match open_config(Path::new(filename)) {
+        Ok(file) => file,
+        Err(e) => {
+            problems.file(format!("Unable to access configuration file: {}", e));
+            return problems;
+        }
+    };
+    files.push(fs::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename)));
+    config_data.files.extend(files.last().cloned());
+    match Format::of(Path::new(filename)) {
+        Format::Xml => load_xml(file, config_data, files, &mut problems),
+        format => load_model(file, format, config_data, files, &mut problems),
+    }
+    files.pop();
+    problems
+}
+
+/// Open a configuration file, or one built in
+pub(crate) fn open_config(filename: &Path) -> io::Result<Box<dyn Read>> {
+    match filename.to_str().and_then(builtin_profile) {
+        Some(text) => Ok(Box::new(text.as_bytes())),
+        None => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    }
+}
+
+/// Loads an XML configuration file
+fn load_xml<R: Read>(
+    file: R,
+    config_data: &mut Simulator,
+    files: &mut Vec<PathBuf>,
+    problems: &mut Problems,
+) {
+    let nested = files.len() > 1; // extended or included by another file
+    let mut parser = EventReader::new_with_config(file, ParserConfig::new().trim_whitespace(true));
+    let mut mode = StartType::Plane;
+    let mut parent = None; // the element containing <target>s being read
+    let mut defaults = Target::new(""); // values from the <switch> for each of its targets
+    let mut command = SwitchCommand::default();
+    let mut ignore = false; // the <switch> is deliberately not used
+    let mut target = Target::new(""); // the <target> being read
+    let mut mag_defaults = MagnetoTarget::new(""); // and the same for <magnetos>
+    let mut magneto = MagnetoCommand::default();
+    let mut mag_target = MagnetoTarget::new("");
+    let mut element = (String::new(), TextPosition::new()); // the element being read, for warnings
+    let mut parent_element = element.clone(); // the parent being read, for warnings
+    let mut content = false; // the element has a property path
+    let mut switches: HashMap<String, u64> = HashMap::new(); // line of each <switch> name
+    let mut complete = false; // the whole file has been read
+    while !complete {
+        match parser.next() {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let mut sname = String::new();
+                element = (format!("<{}>", name.local_name), parser.position());
+                content = false;
+                match name.local_name.as_str() {
+                    "switch" | "starter" | "gear-retarget" => {
+                        let named = name.local_name == "switch"; // the others have no name
+                        defaults = Target::new("");
+                        command = SwitchCommand::default();
+                        ignore = false;
+                        let mut pulse_attribute = false;
+                        for oa in attributes {
+                            pulse_attribute |=
+                                ["duration", "edge"].contains(&oa.name.local_name.as_str());
+                            // println!("Attributes: {:?}", oa);
+                            let result = match oa.name.local_name.as_str() {
+                                "name" if named => {
+                                    sname = oa.value;
+                                    Ok(())
+                                }
+                                "ignore" if named => {
+                                    parse_attribute(&name.local_name, "ignore", &oa.value)
+                                        .map(|value| ignore = value)
+                                }
+                                "mode" => parse_attribute(&name.local_name, "mode", &oa.value)
+                                    .map(|mode| command.mode = mode),
+                                "duration" => {
+                                    parse_attribute(&name.local_name, "duration", &oa.value)
+                                        .map(|ms| command.duration = Duration::from_millis(ms))
+                                }
+                                "edge" => parse_attribute(&name.local_name, "edge", &oa.value)
+                                    .map(|edge| command.edge = edge),
+                                attribute => target_attribute(
+                                    &name.local_name,
+                                    attribute,
+                                    &oa.value,
+                                    &mut defaults,
+                                ),
+                            };
+                            if let Err(message) = result {
+                                problems.at(&parser, message);
+                            }
+                        }
+                        if pulse_attribute && command.mode != SwitchMode::Pulse {
+                            problems.warn(
+                                Some(element.1),
+                                format!(
+                                    "<{}> 'duration' and 'edge' are only used with mode=\"pulse\"",
+                                    name.local_name
+                                ),
+                            );
+                        }
+                        // println!("sname={:?}", sname);
+                        mode = StartType::Switch;
+                        parent = Some(Parent::Switch(if name.local_name == "starter" {
+                            SwitchName::Starter
+                        } else {
+                            SwitchName::GearRetarget
+                        }));
+                        if named {
+                            element.0 = format!("<switch name={:?}>", sname);
+                            let line = element.1.row + 1;
+                            if let Some(first) = switches.insert(sname.clone(), line) {
+                                problems.warn(
+                                    Some(element.1),
+                                    format!(
+                                        "Duplicate SWITCH name {:?}, also at line {}, the last is used",
+                                        sname, first
+                                    ),
+                                );
+                            }
+                            parent = match (
+                                SwitchId::from_name(&sname),
+                                GearPosition::from_name(&sname),
+                            ) {
+                                (Some(id), _) => Some(Parent::Switch(SwitchName::Switch(id))),
+                                (None, Some(position)) => {
+                                    Some(Parent::Switch(SwitchName::Gear(position)))
+                                }
+                                (None, None) => {
+                                    problems
+                                        .at(&parser, format!("Unknown SWITCH name {:?}", sname));
+                                    mode = StartType::Unknown;
+                                    Some(Parent::Unknown)
+                                }
+                            };
+                        }
+                        parent_element = element.clone();
+                    }
+                    "magnetos" => {
+                        mag_defaults = MagnetoTarget::new("");
+                        magneto = MagnetoCommand::default();
+                        for oa in attributes {
+                            if let Err(message) = magneto_attribute(
+                                "magnetos",
+                                &oa.name.local_name,
+                                &oa.value,
+                                &mut mag_defaults,
+                            ) {
+                                problems.at(&parser, message);
+                            }
+                        }
+                        parent = Some(Parent::Magnetos);
+                        parent_element = element.clone();
+                        mode = StartType::Magnetos;
+                    }
+                    "target" => {
+                        mode = StartType::Unknown;
+                        match parent {
+                            Some(Parent::Switch(_)) => {
+                                target = defaults.clone();
+                                for oa in attributes {
+                                    if let Err(message) = target_attribute(
+                                        "target",
+                                        &oa.name.local_name,
+                                        &oa.value,
+                                        &mut target,
+                                    ) {
+                                        problems.at(&parser, message);
+                                    }
+                                }
+                                mode = StartType::Target;
+                            }
+                            Some(Parent::Magnetos) => {
+                                mag_target = mag_defaults.clone();
+                                for oa in attributes {
+                                    if let Err(message) = magneto_attribute(
+                                        "target",
+                                        &oa.name.local_name,
+                                        &oa.value,
+                                        &mut mag_target,
+                                    ) {
+                                        problems.at(&parser, message);
+                                    }
+                                }
+                                mode = StartType::MagnetoTarget;
+                            }
+                            Some(Parent::Unknown) => {}
+                            None => problems.at(
+                                &parser,
+                                "<target> must be inside a <switch>, <magnetos>, <starter> or <gear-retarget>"
+                                    .to_string(),
+                            ),
+                        }
+                    }
+                    "gear-primer" => {
+                        let mut primer = PrimerCommand::new("");
+                        for oa in attributes {
+                            let value = &oa.value;
+                            let result = match oa.name.local_name.as_str() {
+                                "strokes" => parse_attribute("gear-primer", "strokes", value)
+                                    .map(|strokes| primer.strokes = strokes),
+                                "interval" => parse_attribute("gear-primer", "interval", value)
+                                    .map(|interval| {
+                                        primer.interval = Duration::from_millis(interval)
+                                    }),
+                                _ => Err(format!(
+                                    "Unexpected <gear-primer> attribute {:?}",
+                                    oa.name.local_name
+                                )),
+                            };
+                            if let Err(message) = result {
+                                problems.at(&parser, message);
+                            }
+                        }
+                        config_data.gear_primer = Some(primer);
+                        mode = StartType::GearPrimer;
+                    }
+                    "panel" => {
+                        for oa in attributes {
+                            match oa.name.local_name.as_str() {
+                                "serial" => config_data.panel = PanelSelector::Serial(oa.value),
+                                "path" => config_data.panel = PanelSelector::Path(oa.value),
+                                _ => problems.at(
+                                    &parser,
+                                    format!(
+                                        "Unexpected <panel> attribute {:?}",
+                                        oa.name.local_name
+                                    ),
+                                ),
+                            }
+                        }
+                        mode = StartType::Panel;
+                    }
+                    "gear-leds" => {
+                        let mut gear_leds = GearLedConfig::default();
+                        for oa in attributes {
+                            let value = &oa.value;
+                            let result = match oa.name.local_name.as_str() {
+                                "port" => parse_attribute("gear-leds", "port", value)
+                                    .map(|port| gear_leds.port = port),
+                                "nose" => parse_attribute("gear-leds", "nose", value)
+                                    .map(|nose| gear_leds.nose = nose),
+                                "left" => parse_attribute("gear-leds", "left", value)
+                                    .map(|left| gear_leds.left = left),
+                                "right" => parse_attribute("gear-leds", "right", value)
+                                    .map(|right| gear_leds.right = right),
+                                _ => Err(format!(
+                                    "Unexpected <gear-leds> attribute {:?}",
+                                    oa.name.local_name
+                                )),
+                            };
+                            if let Err(message) = result {
+                                problems.at(&parser, message);
+                            }
+                        }
+                        config_data.gear_leds = Some(gear_leds);
+                        mode = StartType::GearLeds;
+                    }
+                    "plane" => {
+                        for oa in attributes {
+                            if oa.name.local_name == "aircraft" {
+                                // only for the file loaded, not those it extends
+                                if !nested {
+                                    config_data.aircraft = aircraft_list(&oa.value);
+                                }
+                            } else if oa.name.local_name == "extends" {
+                                // the file extended comes first, so this file overrides it
+                                load_nested(
+                                    &oa.value,
+                                    Some(parser.position()),
+                                    config_data,
+                                    files,
+                                    problems,
+                                );
+                            } else {
+                                problems.at(
+                                    &parser,
+                                    format!(
+                                        "Unexpected <plane> attribute {:?}",
+                                        oa.name.local_name
+                                    ),
+                                );
+                            }
+                        }
+                        mode = StartType::Plane;
+                    }
+                    "include" => {
+                        let mut included = false;
+                        for oa in attributes {
+                            if oa.name.local_name == "file" {
+                                // later elements in this file override those included
+                                load_nested(
+                                    &oa.value,
+                                    Some(parser.position()),
+                                    config_data,
+                                    files,
+                                    problems,
+                                );
+                                included = true;
+                            } else {
+                                problems.at(
+                                    &parser,
+                                    format!(
+                                        "Unexpected <include> attribute {:?}",
+                                        oa.name.local_name
+                                    ),
+                                );
+                            }
+                        }
+                        if !included {
+                            problems.at(&parser, "<include> needs a 'file' attribute".to_string());
+                        }
+                        mode = StartType::Unknown;
+                    }
+                    _ => {
+                        problems.at(&parser, format!("Unexpected element <{}>", name.local_name));
+                        mode = StartType::Unknown;
+                    }
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) if name.local_name == "target" => {
+                let empty = match mode {
+                    StartType::Target if target.property.is_empty() => true,
+                    StartType::Target => {
+                        command.targets.push(target.clone());
+                        false
+                    }
+                    StartType::MagnetoTarget if mag_target.property.is_empty() => true,
+                    StartType::MagnetoTarget => {
+                        magneto.targets.push(mag_target.clone());
+                        false
+                    }
+                    _ => false,
+                };
+                if empty {
+                    problems.warn(
+                        Some(element.1),
+                        format!("Empty property path for <target> in {}", parent_element.0),
+                    );
+                }
+                mode = match parent {
+                    Some(Parent::Switch(_)) => StartType::Switch,
+                    Some(Parent::Magnetos) => StartType::Magnetos,
+                    _ => StartType::Unknown,
+                };
+            }
+            Ok(XmlEvent::EndElement { name })
+                if ["switch", "starter", "gear-retarget"].contains(&name.local_name.as_str()) =>
+            {
+                match parent.take() {
+                    Some(Parent::Switch(SwitchName::Starter)) => {
+                        config_data.starter = command.clone();
+                    }
+                    Some(Parent::Switch(SwitchName::GearRetarget)) => {
+                        if command.targets.is_empty() {
+                            problems.warn(
+                                Some(parent_element.1),
+                                "Empty property path for <gear-retarget>".to_string(),
+                            );
+                        }
+                        config_data.gear_retarget = command.clone();
+                    }
+                    Some(Parent::Switch(SwitchName::Switch(id))) if ignore => {
+                        config_data.switch_mapper.remove(&id);
+                        config_data.switch_status.remove(&id);
+                        config_data.ignored.push(id);
+                    }
+                    Some(Parent::Switch(SwitchName::Gear(position))) if ignore => {
+                        config_data.gear_mapper.remove(&position);
+                    }
+                    Some(Parent::Switch(_)) if command.targets.is_empty() => problems.warn(
+                        Some(parent_element.1),
+                        format!("Empty property path for {}", parent_element.0),
+                    ),
+                    Some(Parent::Switch(SwitchName::Switch(id))) => {
+                        config_data.switch_mapper.insert(id, command.clone());
+                        config_data.switch_status.insert(id, 0u8);
+                    }
+                    Some(Parent::Switch(SwitchName::Gear(position))) => {
+                        config_data.gear_mapper.insert(position, command.clone());
+                    }
+                    _ => {}
+                }
+                mode = StartType::Unknown;
+            }
+            Ok(XmlEvent::EndElement { name }) if name.local_name == "magnetos" => {
+                config_data.magneto = magneto.clone();
+                parent = None;
+                mode = StartType::Unknown;
+            }
+            Ok(XmlEvent::EndElement { .. }) => {
+                if matches!(mode, StartType::GearPrimer) && !content {
+                    problems.warn(
+                        Some(element.1),
+                        format!("Empty property path for {}", element.0),
+                    );
+                    config_data.gear_primer = None;
+                }
+                mode = StartType::Unknown;
+            }
+            Ok(XmlEvent::Characters(data)) => {
+                content = true;
+                match mode {
+                    StartType::Plane if nested => {}
+                    StartType::Plane => {
+                        println!("Configured for {}", data);
+                        config_data.plane = data;
+                    }
+                    // the property of a <switch> without <target>s
+                    StartType::Switch => command.targets.push(Target {
+                        property: data,
+                        ..defaults.clone()
+                    }),
+                    StartType::Target => target.property = data,
+                    // the property of <magnetos> without <target>s
+                    StartType::Magnetos => magneto.targets.push(MagnetoTarget {
+                        property: data,
+                        ..mag_defaults.clone()
+                    }),
+                    StartType::MagnetoTarget => mag_target.property = data,
+                    StartType::GearPrimer => {
+                        // println!("GearPrimer {:?}", data);
+                        if let Some(primer) = &mut config_data.gear_primer {
+                            primer.property = data;
+                        }
+                    }
+                    StartType::Panel | StartType::GearLeds | StartType::Unknown => {}
+                }
+            }
+            Ok(XmlEvent::EndDocument) => complete = true,
+            Ok(_) => {}
+            Err(e) => {
+                // the rest of the file cannot be read
+                let position = e.position();
+                problems.errors.push(ConfigError {
+                    file: problems.file.clone(),
+                    position: Some((position.row + 1, position.column + 1)),
+                    message: e.msg().to_string(),
+                });
+                return;
+            }
+        }
+    }
+}
+
+/// Load a file extended or included at 'position' (None for the whole file), its path
+/// relative to the file being read. The problems found are added to 'problems'.
+pub(crate) fn load_nested(
+    nested: &str,
+    position: Option<TextPosition>,
+    config_data: &mut Simulator,
+    files: &mut Vec<PathBuf>,
+    problems: &mut Problems,
+) {
+    let path = Path::new(&problems.file)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(nested.trim());
+    let canonical = match fs::canonicalize(&path) {
+        Ok(canonical) => canonical,
+        Err(e) => {
+            problems.error_at(
+                position,
+                format!("Unable to access configuration file {:?}: {}", nested, e),
+            );
+            return;
+        }
+    };
+    if let Some(start) = files.iter().position(|file| *file == canonical) {
+        let cycle: Vec<String> = files[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|file| file.display().to_string())
+            .collect();
+        problems.error_at(
+            position,
+            format!(
+                "Configuration files extend or include each other: {}",
+                cycle.join(" -> ")
+            ),
+        );
+        return;
+    }
+    let found = load_file(&path.display().to_string(), config_data, files);
+    problems.errors.extend(found.errors);
+    problems.warnings.extend(found.warnings);
+}
+
+/// The checks made once the configuration file and those it extends or includes are loaded
+fn check_complete(config_data: &mut Simulator, problems: &mut Problems) {
+    let missing: Vec<&str> = SwitchId::ALL
+        .iter()
+        .filter(|id| {
+            !config_data.switch_mapper.contains_key(id) && !config_data.ignored.contains(id)
+        })
+        .map(|id| id.name())
+        .collect();
+    if !missing.is_empty() {
+        problems.warn(
+            None,
+            format!(
+                "No SWITCH element for {}, which do nothing (ignore=\"true\" leaves a switch unused)",
+                missing.join(", ")
+            ),
+        );
+    }
+    if config_data.magneto.targets.is_empty() {
+        problems.warn(
+            None,
+            "No <magnetos> property, the Magneto switch does nothing".to_string(),
+        );
+    }
+    if config_data.starter.targets.is_empty() {
+        problems.warn(
+            None,
+            "No <starter> property, the starter does nothing".to_string(),
+        );
+    }
+}
+
+/// The aircraft listed by the <plane> element of a configuration file, found without
+/// loading the file. None are listed if the file cannot be read.
+pub(crate) fn plane_aircraft(filename: &Path) -> Vec<String> {
+    match Format::of(filename) {
+        Format::Xml => {}
+        format => return model_aircraft(filename, format),
+    }
+    let file = match open_config(filename) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    for event in EventReader::new(file) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) if name.local_name == "plane" => {
+                return attributes
+                    .iter()
+                    .filter(|oa| oa.name.local_name == "aircraft")
+                    .flat_map(|oa| aircraft_list(&oa.value))
+                    .collect();
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    Vec::new()
+}
+
+/// The aircraft in the <plane> 'aircraft' attribute, separated by spaces or commas
+fn aircraft_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Files;
+    use crate::Value;
+
+    #[test]
+    fn errors_give_line_and_column() {
+        let files = Files::new(
+            "errors",
+            &[(
+                "plane.xml",
+                "<plane>Test\n  <switch name=\"BATERY\">a/b</switch>\n  <switch name=\"ALT\" on=\"x\" off=\"1,2\">a/c</switch>\n  <gear-leds port=\"high\"/>\n</plane>\n",
+            )],
+        );
+        let errors = files.errors("plane.xml");
+        let shown: Vec<String> = errors
+            .iter()
+            .map(|error| error.to_string().replace(&files.path(""), ""))
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                "plane.xml:2:3: Unknown SWITCH name \"BATERY\"",
+                "plane.xml:3:3: Invalid <switch> off \"1,2\": a value may not contain ',' or a newline",
+                "plane.xml:4:3: Invalid <gear-leds> port \"high\": invalid digit found in string",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_switch_warning() {
+        let mut text = String::from("<plane>Plane\n  <switch name=\"ALT\">a/first</switch>\n");
+        for id in switch::SwitchId::ALL {
+            text += &format!("  <switch name=\"{}\">a/{}</switch>\n", id, id);
+        }
+        text += "</plane>\n";
+        let files = Files::new("duplicate", &[("plane.xml", &text)]);
+        let simulator = crate::Simulator::new(&files.path("plane.xml")).unwrap();
+        assert_eq!(
+            simulator.switch_mapper[&switch::SwitchId::Alt],
+            crate::SwitchCommand::new("a/ALT")
+        );
+        let duplicate = &simulator.warnings[0];
+        assert_eq!(duplicate.position, Some((4, 3)));
+        assert_eq!(
+            duplicate.message,
+            "Duplicate SWITCH name \"ALT\", also at line 2, the last is used"
+        );
+    }
+
+    #[test]
+    fn malformed_xml_gives_line_and_column() {
+        let files = Files::new(
+            "malformed",
+            &[(
+                "plane.xml",
+                "<plane>Test\n  <switch name=\"ALT\">a/c</swtch>\n</plane>\n",
+            )],
+        );
+        let errors = files.errors("plane.xml");
+        assert_eq!(errors.len(), 1);
+        // the end of the mismatched end tag
+        assert_eq!(errors[0].position, Some((2, 32)));
+        assert!(errors[0].file.ends_with("plane.xml"));
+    }
+
+    #[test]
+    fn missing_file() {
+        let files = Files::new("missing", &[]);
+        let errors = files.errors("plane.xml");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].position, None);
+        assert!(errors[0]
+            .message
+            .starts_with("Unable to access configuration file"));
+    }
+
+    #[test]
+    fn extends_cycle() {
+        let files = Files::new(
+            "extends",
+            &[
+                (
+                    "a.xml",
+                    "<?xml version=\"1.0\"?>\n<plane extends=\"b.xml\">A</plane>\n",
+                ),
+                (
+                    "b.xml",
+                    "<?xml version=\"1.0\"?>\n<plane extends=\"a.xml\">B</plane>\n",
+                ),
+            ],
+        );
+        let errors = files.errors("a.xml");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].file.ends_with("b.xml"));
+        assert_eq!(errors[0].position, Some((2, 1)));
+        let a = fs::canonicalize(files.path("a.xml")).unwrap();
+        let b = fs::canonicalize(files.path("b.xml")).unwrap();
+        assert_eq!(
+            errors[0].message,
+            format!(
+                "Configuration files extend or include each other: {} -> {} -> {}",
+                a.display(),
+                b.display(),
+                a.display()
+            )
+        );
+    }
+
+    #[test]
+    fn include_cycle() {
+        let files = Files::new(
+            "include",
+            &[("a.xml", "<plane>A\n  <include file=\"a.xml\"/>\n</plane>\n")],
+        );
+        let errors = files.errors("a.xml");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].position, Some((2, 3)));
+        assert!(errors[0]
+            .message
+            .starts_with("Configuration files extend or include each other"));
+    }
+
+    #[test]
+    fn extended_file_is_overridden() {
+        let files = Files::new(
+            "override",
+            &[
+                (
+                    "base.xml",
+                    "<plane aircraft=\"base\">Base\n  <switch name=\"BATTERY\">base/bat</switch>\n  <switch name=\"ALT\">base/alt</switch>\n</plane>\n",
+                ),
+                (
+                    "plane.xml",
+                    "<plane extends=\"base.xml\" aircraft=\"plane\">Plane\n  <switch name=\"ALT\">plane/alt</switch>\n</plane>\n",
+                ),
+            ],
+        );
+        let simulator = files.load("plane.xml");
+        assert_eq!(simulator.aircraft, ["plane"]);
+        assert_eq!(
+            simulator.switch_mapper[&SwitchId::Battery],
+            SwitchCommand::new("base/bat")
+        );
+        assert_eq!(
+            simulator.switch_mapper[&SwitchId::Alt],
+            SwitchCommand::new("plane/alt")
+        );
+    }
+
+    #[test]
+    fn ignored_switches_and_incomplete_warnings() {
+        let files = Files::new(
+            "ignore",
+            &[(
+                "plane.xml",
+                "<plane>Plane\n  <switch name=\"BATTERY\">a/bat</switch>\n  <switch name=\"ALT\" ignore=\"true\"/>\n  <switch name=\"TAXI\" ignore=\"true\"/>\n</plane>\n",
+            )],
+        );
+        let simulator = files.load("plane.xml");
+        assert!(simulator.switch_mapper.contains_key(&SwitchId::Battery));
+        assert!(!simulator.switch_mapper.contains_key(&SwitchId::Alt));
+        let warnings: Vec<&str> = simulator
+            .warnings
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "No SWITCH element for AVIONICS, FUELPUMP, DEICE, PITOTHEAT, COWLCLOSE, PANELLIGHT, BEACON, NAVLIGHTS, STROBE, LANDING, which do nothing (ignore=\"true\" leaves a switch unused)",
+                "No <magnetos> property, the Magneto switch does nothing",
+                "No <starter> property, the starter does nothing",
+            ]
+        );
+        assert!(simulator
+            .warnings
+            .iter()
+            .all(|warning| warning.position.is_none()));
+    }
+
+    #[test]
+    fn targets_with_delays() {
+        let files = Files::new(
+            "targets",
+            &[(
+                "plane.xml",
+                "<plane>Plane\n  <switch name=\"BATTERY\" on=\"0.8\" invert=\"true\">\n    <target>a/bat</target>\n    <target delay=\"200\" on=\"true\" off=\"false\">a/relay</target>\n  </switch>\n</plane>\n",
+            )],
+        );
+        let simulator = files.load("plane.xml");
+        let command = &simulator.switch_mapper[&SwitchId::Battery];
+        assert_eq!(
+            command.targets,
+            [
+                Target {
+                    on: Value::Double(0.8),
+                    invert: true,
+                    ..Target::new("a/bat")
+                },
+                Target {
+                    on: Value::Bool(true),
+                    off: Value::Bool(false),
+                    invert: true,
+                    delay: Duration::from_millis(200),
+                    ..Target::new("a/relay")
+                },
+            ]
+        );
+        assert_eq!(command.targets[0].value(true), &Value::Int(0));
+        assert_eq!(command.targets[1].value(false), &Value::Bool(true));
+    }
+
+    #[test]
+    fn delay_only_on_targets() {
+        let files = Files::new(
+            "delay",
+            &[(
+                "plane.xml",
+                "<plane>Plane\n  <switch name=\"BATTERY\" delay=\"200\">a/bat</switch>\n</plane>\n",
+            )],
+        );
+        let errors = files.errors("plane.xml");
+        assert_eq!(errors[0].position, Some((2, 3)));
+        assert_eq!(errors[0].message, "Unexpected <switch> attribute \"delay\"");
+    }
+
+    #[test]
+    fn magneto_twin_targets() {
+        let files = Files::new(
+            "magnetos",
+            &[(
+                "plane.xml",
+                "<plane>Twin\n  <magnetos start=\"3\">\n    <target>e[0]/magnetos</target>\n    <target delay=\"500\" both=\"5\">e[1]/magnetos</target>\n  </magnetos>\n</plane>\n",
+            )],
+        );
+        let simulator = files.load("plane.xml");
+        let targets = &simulator.magneto.targets;
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].property, "e[0]/magnetos");
+        assert_eq!(targets[0].delay, Duration::ZERO);
+        assert_eq!(targets[1].property, "e[1]/magnetos");
+        assert_eq!(targets[1].delay, Duration::from_millis(500));
+        let values = |target: &MagnetoTarget| -> Vec<Option<Value>> {
+            MagnetoPosition::ALL
+                .iter()
+                .map(|position| target.value(*position).cloned())
+                .collect()
+        };
+        let expected = |both: i64| -> Vec<Option<Value>> {
+            [0, 1, 2, both, 3]
+                .iter()
+                .map(|value| Some(Value::Int(*value)))
+                .collect()
+        };
+        assert_eq!(values(&targets[0]), expected(3));
+        assert_eq!(values(&targets[1]), expected(5));
+    }
+
+    #[test]
+    fn aircraft_listed() {
+        assert_eq!(
+            aircraft_list(" c172p, c172p-ifr  c172p-2d"),
+            ["c172p", "c172p-ifr", "c172p-2d"]
+        );
+        assert!(aircraft_list(" , ").is_empty());
+        let files = Files::new(
+            "aircraft",
+            &[(
+                "plane.xml",
+                "<?xml version=\"1.0\"?>\n<plane aircraft=\"c182s c182t\">Cessna 182</plane>\n",
+            )],
+        );
+        assert_eq!(
+            plane_aircraft(Path::new(&files.path("plane.xml"))),
+            ["c182s", "c182t"]
+        );
+        assert!(plane_aircraft(Path::new(&files.path("missing.xml"))).is_empty());
+    }
+}
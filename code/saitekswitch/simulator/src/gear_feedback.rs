@@ -0,0 +1,197 @@
+//!
+//! Gear position feedback from the simulator, shown on the gear LEDs.
+//!
+//! FGFS sends '/gear/gear[n]/position-norm' for three gear units using the
+//! 'saitekswitch-gear' generic protocol, one line per update:
+//!     0.000000,0.437500,1.000000
+//! Each gear LED shows green when its wheel is down and locked, red while it is
+//! moving, and is dark when it is up.
+//! The listener is stopped when dropped, e.g. when a new aircraft has other gear.
+//!
+
+use switch::{GearLeds, LedColour, LedControl};
+
+use std::io;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const GEAR_FEEDBACK_ADDRESS: &str = "127.0.0.1"; // port from the configuration
+pub const GEAR_FEEDBACK_PORT: u16 = 60004; // don't clash with Radio Panel
+
+// position-norm limits for a wheel fully up, or down and locked
+const GEAR_UP: f32 = 0.01;
+const GEAR_LOCKED: f32 = 0.99;
+
+// wait after a receive error, so that one which persists does not use all the CPU
+pub(crate) const RECEIVE_ERROR_WAIT: Duration = Duration::from_secs(1);
+
+// how often the listener checks whether it has been stopped
+const STOP_CHECK: Duration = Duration::from_millis(200);
+
+/// Configuration of the gear LEDs, from the <gear-leds> element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GearLedConfig {
+    pub port: u16,    // UDP port the simulator sends to
+    pub nose: usize,  // gear unit shown on each LED,
+    pub left: usize,  // i.e. the position in the line
+    pub right: usize, // sent by the simulator
+}
+
+impl Default for GearLedConfig {
+    fn default() -> Self {
+        GearLedConfig {
+            port: GEAR_FEEDBACK_PORT,
+            nose: 0,
+            left: 1,
+            right: 2,
+        }
+    }
+}
+
+impl GearLedConfig {
+    /// Convert a line of gear positions to the LED setting
+    fn leds(&self, line: &str) -> GearLeds {
+        let positions: Vec<Option<f32>> = line
+            .split(',')
+            .map(|position| position.trim().parse().ok())
+            .collect();
+        let colour = |unit: usize| match positions.get(unit).copied().flatten() {
+            Some(position) if position >= GEAR_LOCKED => LedColour::Green,
+            Some(position) if position > GEAR_UP => LedColour::Red,
+            _ => LedColour::Off,
+        };
+        GearLeds {
+            nose: colour(self.nose),
+            left: colour(self.left),
+            right: colour(self.right),
+        }
+    }
+}
+
+/// The thread listening for gear positions, stopped when dropped
+pub struct GearListener {
+    config: GearLedConfig,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GearListener {
+    /// The configuration the listener was started with
+    pub fn config(&self) -> &GearLedConfig {
+        &self.config
+    }
+}
+
+impl Drop for GearListener {
+    /// Stop the thread, and wait for it to finish so that the port is free again
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Start a thread listening for gear positions from the simulator, and setting the
+/// gear LEDs through 'leds'. The thread runs until the returned listener is dropped.
+pub fn spawn_listener(config: GearLedConfig, leds: LedControl) -> io::Result<GearListener> {
+    let socket = UdpSocket::bind((GEAR_FEEDBACK_ADDRESS, config.port))?;
+    socket.set_read_timeout(Some(STOP_CHECK))?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    let gear = config.clone();
+    let thread = thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        let mut last = None;
+        while !stopped.load(Ordering::Relaxed) {
+            let length = match socket.recv(&mut buf) {
+                Ok(length) => length,
+                // nothing from the simulator, check for being stopped
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    continue
+                }
+                Err(e) => {
+                    println!("Gear feedback receive error: {}", e);
+                    thread::sleep(RECEIVE_ERROR_WAIT);
+                    continue;
+                }
+            };
+            // only the latest complete line matters
+            let data = String::from_utf8_lossy(&buf[..length]);
+            if let Some(line) = data.lines().rev().find(|line| !line.is_empty()) {
+                let setting = gear.leds(line);
+                if last != Some(setting) {
+                    leds.set(setting);
+                    last = Some(setting);
+                }
+            }
+        }
+    });
+    Ok(GearListener {
+        config,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_moving_and_locked() {
+        let config = GearLedConfig::default();
+        assert_eq!(config.leds("0.000000,0.000000,0.000000"), GearLeds::OFF);
+        assert_eq!(
+            config.leds("1.000000,1.000000,1.000000"),
+            GearLeds::all(LedColour::Green)
+        );
+        assert_eq!(
+            config.leds("0.010000, 0.437500 ,0.990000"),
+            GearLeds {
+                nose: LedColour::Off,
+                left: LedColour::Red,
+                right: LedColour::Green,
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_or_short_lines() {
+        let config = GearLedConfig::default();
+        assert_eq!(config.leds(""), GearLeds::OFF);
+        assert_eq!(config.leds("up,down,nan?"), GearLeds::OFF);
+        assert_eq!(
+            config.leds("1.0,x"),
+            GearLeds {
+                nose: LedColour::Green,
+                ..GearLeds::OFF
+            }
+        );
+    }
+
+    #[test]
+    fn remapped_units() {
+        // units in another order, with unit 0 not shown
+        let config = GearLedConfig {
+            nose: 3,
+            left: 1,
+            right: 2,
+            ..GearLedConfig::default()
+        };
+        assert_eq!(
+            config.leds("0.5,1.0,0.5,0.0"),
+            GearLeds {
+                nose: LedColour::Off,
+                left: LedColour::Green,
+                right: LedColour::Red,
+            }
+        );
+    }
+}
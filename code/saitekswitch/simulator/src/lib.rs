@@ -29,7 +29,6 @@ THE SOFTWARE.
 //!      BATTERY, ALT, AVIONICS,FUELPUMP, DEICE, PITOTHEAT, COWLCLOSE, PANELLIGHT,
 //!      BEACON, NAVLIGHTS, STROBE, TAXI, LANDING
 //!   rotary Magneto switch
-//!
 //!      MAGOFF, MAGR, MAGL, MAGBOTH, MAGSTART
 //!   a gear lever, up or down
 //!   3 leds (R/O/G) for status indication
@@ -45,133 +44,265 @@ THE SOFTWARE.
 //!   without this affecting the Linux code.
 //!
 
+mod aircraft;
+mod command;
+mod config;
+mod gear_feedback;
+mod model;
+mod profiles;
+mod schedule;
+#[cfg(test)]
+mod testing;
 
-use xml::reader::{EventReader, ParserConfig, XmlEvent};
+pub use aircraft::{find_profile, profile_files, spawn_aircraft_listener, AIRCRAFT_FEEDBACK_PORT};
+pub use command::{
+    MagnetoCommand, MagnetoTarget, PrimerCommand, PulseEdge, SwitchCommand, SwitchMode, Target,
+    Value,
+};
+pub use config::ConfigError;
+pub use gear_feedback::{spawn_listener, GearLedConfig, GearListener};
+pub use model::Format;
+pub use profiles::{find_config, BUILTIN_PREFIX};
 
-use switch::Device;
-use switch_constants::*;
+use config::config_loader;
+use schedule::Schedule;
 
+use switch::{EventKind, GearPosition, MagnetoPosition, PanelEvent, PanelSelector, SwitchId};
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
 use std::net::UdpSocket;
-use std::process;
+use std::path::PathBuf;
+use std::time::Instant;
 use std::{thread, time};
 
-const SIMULATOR_INPUT_ADDRESS: &str = "127.0.0.1:60003"; // don't clash with Radio Panel
+// Any free local port, so neither the Radio Panel nor a second Switch Panel driver clash
+const SIMULATOR_INPUT_ADDRESS: &str = "127.0.0.1:0";
 const SIMULATOR_OUTPUT_ADDRESS: &str = "127.0.0.1:60000"; // don't clash with Radio Panel
 
+// Spacing of the initial settings sent for each control, so the simulator is not flooded
+const INITIAL_SPACING: time::Duration = time::Duration::from_millis(50);
 
 pub struct Simulator {
-    pub switch_mapper: HashMap<u32, String>,
-    pub switch_status: HashMap<u32, u8>,
-    pub magneto: String,
-    pub mag_value: u32,
-    pub mag_mapper: HashMap<u32, u8>,
-    pub starter: String, // switch setting, value
-    pub gear_retarget: String,
-    pub gear_primer: String,
+    pub plane: String,         // the name of the aircraft, from <plane>
+    pub aircraft: Vec<String>, // the simulator aircraft the configuration is for
+    pub files: Vec<PathBuf>, // the configuration files loaded, including those extended or included
+    pub switch_mapper: HashMap<SwitchId, SwitchCommand>,
+    pub switch_status: HashMap<SwitchId, u8>, // the setting of each switch in the simulator, as far as we know
+    pub ignored: Vec<SwitchId>,               // switches deliberately not used
+    pub gear_mapper: HashMap<GearPosition, SwitchCommand>, // GEARUP, GEARDOWN used as switches
+    pub magneto: MagnetoCommand,
+    pub mag_value: Option<MagnetoPosition>,
+    pub starter: SwitchCommand,       // on in the Magneto START position
+    pub gear_retarget: SwitchCommand, // the gear lever as a switch, on when up
+    pub gear_primer: Option<PrimerCommand>, // the gear lever as a primer pump
+    pub panel: PanelSelector,         // which panel to use when several are attached
+    pub gear_leds: Option<GearLedConfig>, // None for fixed gear
+    pub warnings: Vec<ConfigError>,   // mistakes in the configuration file which were ignored
+    schedule: Schedule,               // writes waiting for their time
 }
 
 impl Simulator {
     /// Create and initialise the simulator mapping structure
     /// by loading the configuration file and forming suitable
-    /// data structures for easy access
-    pub fn new(config_file: &str) -> Simulator {
+    /// data structures for easy access.
+    /// Returns every problem found in the configuration file.
+    pub fn new(config_file: &str) -> Result<Simulator, Vec<ConfigError>> {
         let mut sim_map = Simulator {
+            plane: String::new(),
+            aircraft: Vec::new(),
+            files: Vec::new(),
             switch_mapper: HashMap::new(),
             switch_status: HashMap::new(),
-            magneto: String::new(),
-            mag_mapper: HashMap::new(),
-            mag_value: 0,
-            starter: String::new(),
-            gear_retarget: String::new(),
-            gear_primer: String::new(),
+            ignored: Vec::new(),
+            gear_mapper: HashMap::new(),
+            magneto: MagnetoCommand::default(),
+            mag_value: None,
+            starter: SwitchCommand::default(),
+            gear_retarget: SwitchCommand::default(),
+            gear_primer: None,
+            panel: PanelSelector::First,
+            gear_leds: None,
+            warnings: Vec::new(),
+            schedule: Schedule::default(),
         };
 
-        config_loader(config_file, Device::make_name_map(), &mut sim_map);
+        config_loader(config_file, &mut sim_map)?;
 
-        sim_map
+        Ok(sim_map)
     }
     /// Set up initial values for the switches in the simulator
-    /// using the current switch values (set up by the initial read)
-    pub fn initialise_switches(&mut self, current_input: u32) {
-        // Set initial state for all the switches
-        // send the initial state for all the switches to the simulator
-        // need to both store and iterate this list -  hashmap with
-        // bit mask and current value -switch_status
-        let delay = time::Duration::from_millis(50);
-        // println!(
-        //     "Enter initialise_switches - size of switch_mapper {}",
-        //     &self.switch_mapper.len()
-        // );
-        for (key, value) in &self.switch_mapper {
-            let status = if (current_input & key) == 0 { 0 } else { 1 };
-            let _ = &self.switch_status.insert(*key, status);
-            // println!("Key {:06x} Value {} destination {}", key, status, value);
-            write_simulator(value, status);
-            thread::sleep(delay);
+    /// using the events giving the complete panel settings (from the initial read).
+    /// Also used to resend all the switch values when the panel is reconnected.
+    /// Switches which pulse are left alone, as nothing has been operated, and switches
+    /// which toggle are only toggled if they are not already set in the simulator.
+    /// The settings are scheduled INITIAL_SPACING apart, and sent by 'tick' while the
+    /// panel is read.
+    pub fn initialise_switches(&mut self, state: &[PanelEvent]) {
+        // send the initial state for all the switches to the simulator,
+        // and record it in switch_status
+        let mut start = Instant::now();
+        self.schedule.set_initialising(true);
+        for event in state {
+            match event.kind {
+                EventKind::Switch { id, on } => {
+                    match self.switch_mapper.get(&id).map(|command| command.mode) {
+                        Some(SwitchMode::Pulse) => {
+                            self.switch_status.insert(id, u8::from(on));
+                        }
+                        Some(_) => self.switch(id, on, start),
+                        None => {}
+                    }
+                }
+                EventKind::GearLever(position) => {
+                    self.gear_lever(position, true, start);
+                }
+                EventKind::Magneto(position) => {
+                    self.schedule.magneto(&self.magneto, position, start);
+                    if position == MagnetoPosition::Start {
+                        self.schedule.switch(&self.starter, true, start);
+                    } else if self.mag_value == Some(MagnetoPosition::Start) {
+                        // starter left running before a reconnection
+                        self.schedule.switch(&self.starter, false, start);
+                    }
+                    self.mag_value = Some(position);
+                }
+            }
+            start += INITIAL_SPACING;
         }
-        // Magneto 0..5
-        for (key, value) in &self.mag_mapper {
-            if (current_input & key) != 0 {
-                write_simulator(&self.magneto, *value);
-                self.mag_value = *key;
-                //    println!(
-                //         "Initialise_switches: Magneto value preserved is 0x{:06x}",
-                //         self.mag_value
-                //     );
-                break;
+        self.schedule.set_initialising(false);
+        self.tick();
+    }
+
+    /// Replace the configuration with 'new', the configuration file loaded again.
+    /// The panel settings in 'state' are sent again for the controls whose
+    /// configuration has changed; the others are left as they are.
+    pub fn reload(&mut self, mut new: Simulator, state: &[PanelEvent]) {
+        self.finish();
+        new.mag_value = self.mag_value;
+        for (id, status) in &self.switch_status {
+            if new.switch_mapper.get(id) == self.switch_mapper.get(id) {
+                new.switch_status.insert(*id, *status);
             }
         }
+        let changed: Vec<PanelEvent> = state
+            .iter()
+            .filter(|event| match event.kind {
+                EventKind::Switch { id, .. } => {
+                    new.switch_mapper.get(&id) != self.switch_mapper.get(&id)
+                }
+                EventKind::GearLever(_) => {
+                    new.gear_mapper != self.gear_mapper || new.gear_retarget != self.gear_retarget
+                }
+                EventKind::Magneto(_) => new.magneto != self.magneto || new.starter != self.starter,
+            })
+            .copied()
+            .collect();
+        *self = new;
+        self.initialise_switches(&changed);
     }
 
-    /// Send suitable command for the (change in) input data
-    /// Current and previous values are incoming paramters
-    pub fn process_input(&mut self, current_input: u32, previous_input: u32) {
-        // Let's do SWITCHES
-        let key = (current_input ^ previous_input) & SWITCHMASK;
-        // println!("process_input key={:06x}", key);
-        if key != 0 {
-            // only one bit changes per read - OR NOT, bug from Bruce Maggs
-            for lkey in self.switch_mapper.keys() {
-                if (lkey & key) != 0 {
-                    // single bit match
-                    let command = self.switch_mapper.get(lkey).unwrap();
-                    let value = self.switch_status.entry(*lkey).or_insert(0);
-                    *value = if *value == 0 { 1u8 } else { 0u8 };
-                    write_simulator(command, *value);
-                    if key == *lkey {
-                        break; // quit if only one bit to match
-                    }
+    /// Send suitable command for a change on the panel
+    pub fn process_event(&mut self, event: &PanelEvent) {
+        match event.kind {
+            // Let's do SWITCHES
+            EventKind::Switch { id, on } => self.switch(id, on, Instant::now()),
+            EventKind::GearLever(position) => {
+                self.gear_lever(position, false, Instant::now());
+                if position == GearPosition::Down {
+                    self.prime();
+                }
+            }
+            // let's do MAGNETOS
+            EventKind::Magneto(position) => {
+                if self.mag_value == Some(position) {
+                    return;
+                }
+                // switch changed position
+                if self.mag_value == Some(MagnetoPosition::Start) {
+                    // is starter running? turn off starter now
+                    self.schedule.switch(&self.starter, false, Instant::now());
+                }
+                self.mag_value = Some(position);
+                self.schedule
+                    .magneto(&self.magneto, position, Instant::now());
+                if position == MagnetoPosition::Start {
+                    // extra action on the starter
+                    self.schedule.switch(&self.starter, true, Instant::now());
                 }
             }
         }
+        self.tick();
+    }
+
+    /// Send the writes to the simulator which are now due.
+    /// Called regularly, so that delayed targets, primer strokes and the ends
+    /// of pulses are sent on time.
+    pub fn tick(&mut self) {
+        self.schedule.send_due(Instant::now(), send_simulator);
+    }
+
+    /// How long until the next write to the simulator is due, None if there are none
+    pub fn until_due(&self) -> Option<time::Duration> {
+        self.schedule
+            .next_due()
+            .map(|due| due.saturating_duration_since(Instant::now()))
+    }
+
+    /// The configuration as the text of a TOML or JSON file, with the files it extends
+    /// or includes merged in, and only the settings which are not the default
+    pub fn convert(&self, format: Format) -> Result<String, String> {
+        model::ConfigModel::from_simulator(self).to_text(format)
+    }
+
+    /// Wait for all the writes to the simulator to be sent, when finishing
+    pub fn finish(&mut self) {
+        while let Some(wait) = self.until_due() {
+            thread::sleep(wait);
+            self.tick();
+        }
+    }
 
-        // let's do MAGNETOS
-        // println!("process_input: Magnetos? {:06x} nag_value {:06x}", device.get_current_input() & MAGMASK, self.mag_value);
-        let key = current_input & MAGMASK;
-        if (key != 0) && (key != self.mag_value) {
-            // switch changed position
-            if self.mag_value == MAGSTART {
-                // is starter running? turn off starter now
-                write_simulator(&self.starter, 0);
+    /// Operate one of the 13 switches from 'start', recording its setting in the simulator
+    fn switch(&mut self, id: SwitchId, on: bool, start: Instant) {
+        if let Some(command) = self.switch_mapper.get(&id) {
+            let status = self.switch_status.insert(id, u8::from(on));
+            if command.mode != SwitchMode::Toggle || status != Some(u8::from(on)) {
+                self.schedule.switch(command, on, start);
             }
-            // println!("process_input: key {:06x} mag_value {:06x}", key, self.mag_value);
-            self.mag_value = key;
-            write_simulator(&self.magneto, *self.mag_mapper.get(&key).unwrap());
-            if key == MAGSTART {
-                write_simulator(&self.starter, 1); // extra action on the starter
+        }
+    }
+
+    /// The gear lever acts as two switches, GEARUP on when the lever is up,
+    /// and GEARDOWN on when it is down, and as the 'gear-retarget' switch,
+    /// on when the lever is up. For the initial lever position, only switches which
+    /// set a level are sent. The switches are sent from 'start'.
+    fn gear_lever(&mut self, position: GearPosition, initial: bool, start: Instant) {
+        let commands = self
+            .gear_mapper
+            .iter()
+            .map(|(switch, command)| (command, *switch == position))
+            .chain([(&self.gear_retarget, position == GearPosition::Up)]);
+        for (command, on) in commands {
+            if !initial || command.mode == SwitchMode::Level {
+                self.schedule.switch(command, on, start);
             }
         }
     }
+
+    /// Pump the primer, if the gear lever is used as a primer.
+    /// Not done for the initial lever position, only when the lever is moved.
+    fn prime(&mut self) {
+        if let Some(primer) = &self.gear_primer {
+            self.schedule
+                .strokes(&primer.property, primer.strokes, primer.interval);
+        }
+    }
 }
 
-/// Send a command to the FGFS consisting of the simulator name for the switch to operate
-///  and the action (which is one of 0, 1, 2, 3, 4)
-fn write_simulator(control: &str, action: u8) {
-    let data = format!("{},{}\n", control, action);
+/// Send a command to the FGFS: the property, the action type, which may also be 'add'
+/// to add the action to the property, and the action
+fn send_simulator(control: &str, action_type: &str, action: &str) {
+    let data = format!("{},{},{}\n", control, action_type, action);
     // println!("Writing {}", data);
     let buf = data.into_bytes();
     // Following required to avoid getting 'address in use' error
@@ -182,104 +313,3 @@ fn write_simulator(control: &str, action: u8) {
         .send_to(&buf, SIMULATOR_OUTPUT_ADDRESS)
         .expect("Socket send error");
 }
-
-// ------------------------------------------------------------------------------------------------------------------------
-// CONFIGURATION processing
-// ------------------------------------------------------------------------------------------------------------------------
-
-// Tag names in XML configuration file
-enum StartType {
-    Plane,
-    Switch,
-    Magnetos,
-    Starter,
-    GearRetarget,
-    GearPrimer,
-}
-
-/// Processes the configuration file to build the mapping tables in the simulator
-/// This is called from 'new', so there is no 'self' yet
-fn config_loader(filename: &str, devmap: HashMap<String, u32>, config_data: &mut Simulator) {
-    let file = File::open(filename).unwrap_or_else(|_e| {
-        println!("Unable to access configuration file '{}'", filename);
-        process::exit(4);
-    });
-    let file = BufReader::new(file);
-
-    let parser = EventReader::new_with_config(file, ParserConfig::new().trim_whitespace(true));
-    let mut mode = StartType::Plane;
-    let mut switch: u32 = 0;
-    for e in parser {
-        match e {
-            Ok(XmlEvent::StartElement {
-                name, attributes, ..
-            }) => {
-                let mut sname = String::new();
-                match name.local_name.as_str() {
-                    "switch" => {
-                        for oa in attributes {
-                            // println!("Attributes: {:?}", oa);
-                            if oa.name.local_name == "name" {
-                                sname = oa.value;
-                            }
-                        }
-                        // println!("sname={:?}", sname);
-                        match devmap.get(&sname) {
-                            Some(x) => switch = *x,
-                            None => panic!("Unknown SWITCH name {:?}", sname),
-                        }
-                        mode = StartType::Switch;
-                    }
-                    "magnetos" => mode = StartType::Magnetos,
-                    "starter" => mode = StartType::Starter,
-                    "gear-retarget" => mode = StartType::GearRetarget,
-                    "gear-primer" => mode = StartType::GearPrimer,
-                    "plane" => mode = StartType::Plane,
-                    _ => panic!("Unexpected element <{}>", name.local_name),
-                }
-            }
-            Ok(XmlEvent::Characters(data)) => match mode {
-                StartType::Plane => {
-                    println!("Configured for {}", data);
-                }
-                StartType::Switch => {
-                    // println!("0x{:06x}={}", switch, data);
-                    config_data.switch_mapper.insert(switch, data);
-                    config_data.switch_status.insert(switch, 0u8);
-                }
-                StartType::Magnetos => {
-                    // println!("Magneto=\"{}\"", data);
-                    config_data.magneto = data;
-                }
-                StartType::Starter => {
-                    // println!("Starter=\"{}\"", data);
-                    config_data.starter = data;
-                }
-                StartType::GearRetarget => {
-                    // println!("GearRetarget{:?}", data);
-                    config_data.gear_retarget = data;
-                }
-                StartType::GearPrimer => {
-                    // println!("GearPrimer {:?}", data);
-                    config_data.gear_primer = data;
-                }
-            },
-            Err(e) => {
-                println!("Error: {}", e);
-                break;
-            }
-            _ => {}
-        }
-    }
-    if config_data.switch_mapper.len() < 13 {
-        panic!(
-            "Error: you need 13 SWITCH elements, only {} unique ones provided",
-            config_data.switch_mapper.len()
-        );
-    }
-    config_data.mag_mapper.insert(MAGOFF, 0);
-    config_data.mag_mapper.insert(MAGR, 1);
-    config_data.mag_mapper.insert(MAGL, 2);
-    config_data.mag_mapper.insert(MAGBOTH, 3);
-    config_data.mag_mapper.insert(MAGSTART, 4);
-}
@@ -0,0 +1,739 @@
+//!
+//! The configuration in TOML or JSON, read with serde, as an alternative to the XML
+//! file. The settings are those of the XML elements and attributes, e.g.
+//!     plane = "Cessna 172P"
+//!     aircraft = ["c172p"]
+//!     [switches.BATTERY]
+//!     property = "controls/switches/master-bat"
+//!     [switches.PANELLIGHT]
+//!     property = "controls/lighting/instruments-norm"
+//!     on = 0.8
+//!     [magnetos]
+//!     property = "controls/switches/magnetos"
+//!
+//! The format is chosen by the file extension, '.toml' or '.json'; any other file is XML.
+//! 'convert' writes a loaded configuration in either format.
+//!
+
+use crate::config::{load_nested, open_config, Problems};
+use crate::{
+    GearLedConfig, MagnetoCommand, MagnetoTarget, PrimerCommand, PulseEdge, Simulator,
+    SwitchCommand, SwitchMode, Target, Value,
+};
+
+use switch::{GearPosition, MagnetoPosition, PanelSelector, SwitchId};
+
+use serde::{Deserialize, Serialize};
+use xml::common::TextPosition;
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The format of a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Xml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// The format for the file extension, XML unless '.toml' or '.json'
+    pub fn of(path: &Path) -> Format {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("json") => Format::Json,
+            _ => Format::Xml,
+        }
+    }
+}
+
+/// A configuration file in TOML or JSON
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>, // loaded first, then replaced by this file
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>, // loaded after 'extends', then replaced by this file
+    pub plane: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aircraft: Vec<String>,
+    pub switches: BTreeMap<String, SwitchModel>, // by SWITCH name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub magnetos: Option<MagnetoModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starter: Option<SwitchModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gear_retarget: Option<SwitchModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gear_primer: Option<PrimerModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panel: Option<PanelModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gear_leds: Option<GearLedsModel>,
+}
+
+/// A <switch>, <starter> or <gear-retarget>: the property, or the targets, and the
+/// values for them all
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub property: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invert: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<SwitchMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>, // milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edge: Option<PulseEdge>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<TargetModel>,
+}
+
+/// A <target> of a switch
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetModel {
+    pub property: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invert: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>, // milliseconds
+}
+
+/// The <magnetos>: the property, or the targets, and the values for the positions
+/// which are not the default
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MagnetoModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub property: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub both: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<MagnetoTargetModel>,
+}
+
+/// A <target> of the magnetos
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MagnetoTargetModel {
+    pub property: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub both: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>, // milliseconds
+}
+
+/// The <gear-primer>
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrimerModel {
+    pub property: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strokes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>, // milliseconds
+}
+
+/// The <panel>
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PanelModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// The <gear-leds>
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GearLedsModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nose: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<usize>,
+}
+
+//-------------------------------------------------------------------------------
+
+/// Loads a TOML or JSON configuration file
+pub(crate) fn load_model(
+    mut file: impl Read,
+    format: Format,
+    config_data: &mut Simulator,
+    files: &mut Vec<PathBuf>,
+    problems: &mut Problems,
+) {
+    let mut text = String::new();
+    if let Err(e) = file.read_to_string(&mut text) {
+        problems.file(format!("Unable to read configuration file: {}", e));
+        return;
+    }
+    match parse(&text, format) {
+        Ok(model) => model.apply(config_data, files, problems),
+        Err((position, message)) => problems.error_at(position, message),
+    }
+}
+
+/// Parse the text of a file, or give the position of the mistake, and what it is
+fn parse(text: &str, format: Format) -> Result<ConfigModel, (Option<TextPosition>, String)> {
+    match format {
+        Format::Toml => toml::from_str(text).map_err(|e| {
+            let position = e.span().map(|span| text_position(text, span.start));
+            (position, e.message().trim().to_string())
+        }),
+        _ => serde_json::from_str(text).map_err(|e| {
+            let position = TextPosition {
+                row: (e.line() as u64).saturating_sub(1),
+                column: (e.column() as u64).saturating_sub(1),
+            };
+            // the message without the position, which is given separately
+            let message = e.to_string();
+            let message = match message.rsplit_once(" at line ") {
+                Some((message, _)) => message.to_string(),
+                None => message,
+            };
+            (Some(position), message)
+        }),
+    }
+}
+
+/// The line and column of a byte offset in the text
+fn text_position(text: &str, offset: usize) -> TextPosition {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    TextPosition {
+        row: before.matches('\n').count() as u64,
+        column: before[line_start..].chars().count() as u64,
+    }
+}
+
+/// The aircraft listed in a TOML or JSON configuration file, none if it cannot be read
+pub(crate) fn model_aircraft(filename: &Path, format: Format) -> Vec<String> {
+    let mut text = String::new();
+    match open_config(filename).and_then(|mut file| file.read_to_string(&mut text)) {
+        Ok(_) => parse(&text, format)
+            .map(|model| model.aircraft)
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+impl ConfigModel {
+    /// Set up the mapping tables as for the XML elements
+    fn apply(self, config_data: &mut Simulator, files: &mut Vec<PathBuf>, problems: &mut Problems) {
+        // the files extended and included come first, so this file replaces them
+        for nested in self.extends.iter().chain(self.include.iter()) {
+            load_nested(nested, None, config_data, files, problems);
+        }
+        // only for the file loaded, not those it extends or includes
+        if files.len() == 1 && !self.plane.is_empty() {
+            println!("Configured for {}", self.plane);
+            config_data.plane = self.plane;
+        }
+        if files.len() == 1 && !self.aircraft.is_empty() {
+            config_data.aircraft = self.aircraft;
+        }
+        for (name, switch) in &self.switches {
+            let ignore = switch.ignore == Some(true);
+            match (SwitchId::from_name(name), GearPosition::from_name(name)) {
+                (Some(id), _) if ignore => {
+                    config_data.switch_mapper.remove(&id);
+                    config_data.switch_status.remove(&id);
+                    config_data.ignored.push(id);
+                }
+                (None, Some(position)) if ignore => {
+                    config_data.gear_mapper.remove(&position);
+                }
+                (Some(_), _) | (None, Some(_)) => {
+                    let what = format!("switch {}", name);
+                    let command = switch.command(&what, problems);
+                    if command.targets.is_empty() {
+                        problems.warn(None, format!("Empty property path for {}", what));
+                    } else if let Some(id) = SwitchId::from_name(name) {
+                        config_data.switch_mapper.insert(id, command);
+                        config_data.switch_status.insert(id, 0u8);
+                    } else if let Some(position) = GearPosition::from_name(name) {
+                        config_data.gear_mapper.insert(position, command);
+                    }
+                }
+                (None, None) => problems.file(format!("Unknown SWITCH name {:?}", name)),
+            }
+        }
+        if let Some(starter) = &self.starter {
+            config_data.starter = starter.command("starter", problems);
+        }
+        if let Some(gear_retarget) = &self.gear_retarget {
+            config_data.gear_retarget = gear_retarget.command("gear-retarget", problems);
+            if config_data.gear_retarget.targets.is_empty() {
+                problems.warn(None, "Empty property path for gear-retarget".to_string());
+            }
+        }
+        if let Some(magnetos) = &self.magnetos {
+            config_data.magneto = magnetos.command(problems);
+        }
+        if let Some(primer) = self.gear_primer {
+            config_data.gear_primer = if primer.property.is_empty() {
+                problems.warn(None, "Empty property path for gear-primer".to_string());
+                None
+            } else {
+                let mut command = PrimerCommand::new(&primer.property);
+                command.strokes = primer.strokes.unwrap_or(command.strokes);
+                command.interval = primer
+                    .interval
+                    .map_or(command.interval, Duration::from_millis);
+                Some(command)
+            };
+        }
+        if let Some(panel) = self.panel {
+            match (panel.serial, panel.path) {
+                (Some(serial), None) => config_data.panel = PanelSelector::Serial(serial),
+                (None, Some(path)) => config_data.panel = PanelSelector::Path(path),
+                (None, None) => {}
+                (Some(_), Some(_)) => {
+                    problems.file("panel may have a serial or a path, not both".to_string())
+                }
+            }
+        }
+        if let Some(leds) = self.gear_leds {
+            let mut gear_leds = GearLedConfig::default();
+            gear_leds.port = leds.port.unwrap_or(gear_leds.port);
+            gear_leds.nose = leds.nose.unwrap_or(gear_leds.nose);
+            gear_leds.left = leds.left.unwrap_or(gear_leds.left);
+            gear_leds.right = leds.right.unwrap_or(gear_leds.right);
+            config_data.gear_leds = Some(gear_leds);
+        }
+    }
+
+    /// The model of a loaded configuration, the files it extends or includes merged
+    pub fn from_simulator(simulator: &Simulator) -> ConfigModel {
+        let mut switches = BTreeMap::new();
+        for (id, command) in &simulator.switch_mapper {
+            switches.insert(id.name().to_string(), SwitchModel::from_command(command));
+        }
+        for id in &simulator.ignored {
+            if !simulator.switch_mapper.contains_key(id) {
+                let ignore = SwitchModel {
+                    ignore: Some(true),
+                    ..SwitchModel::default()
+                };
+                switches.insert(id.name().to_string(), ignore);
+            }
+        }
+        for (position, command) in &simulator.gear_mapper {
+            switches.insert(
+                position.name().to_string(),
+                SwitchModel::from_command(command),
+            );
+        }
+        let switch = |command: &SwitchCommand| {
+            (!command.targets.is_empty()).then(|| SwitchModel::from_command(command))
+        };
+        ConfigModel {
+            extends: None,
+            include: Vec::new(),
+            plane: simulator.plane.clone(),
+            aircraft: simulator.aircraft.clone(),
+            switches,
+            magnetos: (!simulator.magneto.targets.is_empty())
+                .then(|| MagnetoModel::from_command(&simulator.magneto)),
+            starter: switch(&simulator.starter),
+            gear_retarget: switch(&simulator.gear_retarget),
+            gear_primer: simulator.gear_primer.as_ref().map(|primer| PrimerModel {
+                property: primer.property.clone(),
+                strokes: Some(primer.strokes),
+                interval: Some(primer.interval.as_millis() as u64),
+            }),
+            panel: match &simulator.panel {
+                PanelSelector::First => None,
+                PanelSelector::Serial(serial) => Some(PanelModel {
+                    serial: Some(serial.clone()),
+                    path: None,
+                }),
+                PanelSelector::Path(path) => Some(PanelModel {
+                    serial: None,
+                    path: Some(path.clone()),
+                }),
+            },
+            gear_leds: simulator.gear_leds.as_ref().map(|leds| GearLedsModel {
+                port: Some(leds.port),
+                nose: Some(leds.nose),
+                left: Some(leds.left),
+                right: Some(leds.right),
+            }),
+        }
+    }
+
+    /// The text of the file in the format
+    pub fn to_text(&self, format: Format) -> Result<String, String> {
+        match format {
+            Format::Toml => toml::to_string_pretty(self).map_err(|e| e.to_string()),
+            Format::Json => serde_json::to_string_pretty(self)
+                .map(|text| text + "\n")
+                .map_err(|e| e.to_string()),
+            Format::Xml => Err("conversion to XML is not supported".to_string()),
+        }
+    }
+}
+
+/// A value, checked it can be sent to the simulator
+fn value(value: &Option<Value>, what: &str, name: &str, problems: &mut Problems) -> Option<Value> {
+    let value = value.as_ref()?;
+    match value.check() {
+        Ok(()) => Some(value.clone()),
+        Err(message) => {
+            problems.file(format!("Invalid {} {} {}: {}", what, name, value, message));
+            None
+        }
+    }
+}
+
+impl SwitchModel {
+    /// The command for the switch, 'what' naming it for problems
+    fn command(&self, what: &str, problems: &mut Problems) -> SwitchCommand {
+        let mut defaults = Target::new("");
+        if let Some(on) = value(&self.on, what, "on", problems) {
+            defaults.on = on;
+        }
+        if let Some(off) = value(&self.off, what, "off", problems) {
+            defaults.off = off;
+        }
+        defaults.invert = self.invert.unwrap_or(defaults.invert);
+
+        let mut command = SwitchCommand::default();
+        command.mode = self.mode.unwrap_or(command.mode);
+        command.duration = self
+            .duration
+            .map_or(command.duration, Duration::from_millis);
+        command.edge = self.edge.unwrap_or(command.edge);
+        if (self.duration.is_some() || self.edge.is_some()) && command.mode != SwitchMode::Pulse {
+            problems.warn(
+                None,
+                format!(
+                    "{} 'duration' and 'edge' are only used with mode \"pulse\"",
+                    what
+                ),
+            );
+        }
+        if let Some(property) = &self.property {
+            command.targets.push(Target {
+                property: property.clone(),
+                ..defaults.clone()
+            });
+        }
+        for target in &self.targets {
+            if target.property.is_empty() {
+                problems.warn(None, format!("Empty property path for target in {}", what));
+                continue;
+            }
+            let mut command_target = Target {
+                property: target.property.clone(),
+                ..defaults.clone()
+            };
+            if let Some(on) = value(&target.on, what, "on", problems) {
+                command_target.on = on;
+            }
+            if let Some(off) = value(&target.off, what, "off", problems) {
+                command_target.off = off;
+            }
+            command_target.invert = target.invert.unwrap_or(command_target.invert);
+            command_target.delay = target.delay.map_or(Duration::ZERO, Duration::from_millis);
+            command.targets.push(command_target);
+        }
+        command
+    }
+
+    /// The model of a switch command, with the values which are not the default
+    fn from_command(command: &SwitchCommand) -> SwitchModel {
+        let default = Target::new("");
+        let differ = |value: &Value, default: &Value| (value != default).then(|| value.clone());
+        let mut model = SwitchModel::default();
+        match command.targets.as_slice() {
+            [target] if target.delay.is_zero() => {
+                model.property = Some(target.property.clone());
+                model.on = differ(&target.on, &default.on);
+                model.off = differ(&target.off, &default.off);
+                model.invert = target.invert.then_some(true);
+            }
+            targets => {
+                model.targets = targets
+                    .iter()
+                    .map(|target| TargetModel {
+                        property: target.property.clone(),
+                        on: differ(&target.on, &default.on),
+                        off: differ(&target.off, &default.off),
+                        invert: target.invert.then_some(true),
+                        delay: (!target.delay.is_zero()).then_some(target.delay.as_millis() as u64),
+                    })
+                    .collect();
+            }
+        }
+        if command.mode != SwitchMode::Level {
+            model.mode = Some(command.mode);
+        }
+        if command.mode == SwitchMode::Pulse {
+            model.duration = Some(command.duration.as_millis() as u64);
+            model.edge = Some(command.edge);
+        }
+        model
+    }
+}
+
+/// Set the values given for the positions, in the order of 'MagnetoPosition::ALL'
+fn set_magneto_values(
+    values: [&Option<Value>; 5],
+    target: &mut MagnetoTarget,
+    problems: &mut Problems,
+) {
+    for (position, given) in MagnetoPosition::ALL.iter().zip(values) {
+        let name = position.name().to_lowercase();
+        if let Some(value) = value(given, "magnetos", &name, problems) {
+            target.values.insert(*position, value);
+        }
+    }
+}
+
+/// The values of a target which are not the default, in the order of 'MagnetoPosition::ALL'
+fn magneto_values(target: &MagnetoTarget) -> [Option<Value>; 5] {
+    let default = MagnetoTarget::new("");
+    MagnetoPosition::ALL.map(|position| {
+        let value = target.value(position);
+        if value != default.value(position) {
+            value.cloned()
+        } else {
+            None
+        }
+    })
+}
+
+impl MagnetoModel {
+    /// The command for the Magneto switch
+    fn command(&self, problems: &mut Problems) -> MagnetoCommand {
+        let mut defaults = MagnetoTarget::new("");
+        let values = [&self.off, &self.r, &self.l, &self.both, &self.start];
+        set_magneto_values(values, &mut defaults, problems);
+        let mut command = MagnetoCommand::default();
+        if let Some(property) = &self.property {
+            command.targets.push(MagnetoTarget {
+                property: property.clone(),
+                ..defaults.clone()
+            });
+        }
+        for target in &self.targets {
+            if target.property.is_empty() {
+                problems.warn(
+                    None,
+                    "Empty property path for target in magnetos".to_string(),
+                );
+                continue;
+            }
+            let mut command_target = MagnetoTarget {
+                property: target.property.clone(),
+                ..defaults.clone()
+            };
+            let values = [
+                &target.off,
+                &target.r,
+                &target.l,
+                &target.both,
+                &target.start,
+            ];
+            set_magneto_values(values, &mut command_target, problems);
+            command_target.delay = target.delay.map_or(Duration::ZERO, Duration::from_millis);
+            command.targets.push(command_target);
+        }
+        command
+    }
+
+    /// The model of the Magneto command, with the values which are not the default
+    fn from_command(command: &MagnetoCommand) -> MagnetoModel {
+        match command.targets.as_slice() {
+            [target] if target.delay.is_zero() => {
+                let [off, r, l, both, start] = magneto_values(target);
+                MagnetoModel {
+                    property: Some(target.property.clone()),
+                    off,
+                    r,
+                    l,
+                    both,
+                    start,
+                    targets: Vec::new(),
+                }
+            }
+            targets => MagnetoModel {
+                targets: targets
+                    .iter()
+                    .map(|target| {
+                        let [off, r, l, both, start] = magneto_values(target);
+                        MagnetoTargetModel {
+                            property: target.property.clone(),
+                            off,
+                            r,
+                            l,
+                            both,
+                            start,
+                            delay: (!target.delay.is_zero())
+                                .then_some(target.delay.as_millis() as u64),
+                        }
+                    })
+                    .collect(),
+                ..MagnetoModel::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Files;
+    use crate::BUILTIN_PREFIX;
+
+    // a configuration using every element, with values which are not the defaults
+    const EVERYTHING: &str = r#"<?xml version="1.0"?>
+<plane aircraft="twin twin-ifr">Twin
+    <switch name="BATTERY" on="0.8" invert="true">
+        <target>controls/switches/master-bat</target>
+        <target delay="200" on="true" off="false">controls/electric/bus-relay</target>
+    </switch>
+    <switch name="ALT" mode="pulse" duration="500" edge="both">controls/switches/alt-test</switch>
+    <switch name="AVIONICS" mode="toggle">controls/switches/avionics</switch>
+    <switch name="FUELPUMP" on="on" off="off">controls/fuel/pump</switch>
+    <switch name="TAXI" ignore="true"/>
+    <switch name="GEARUP">controls/gear/up</switch>
+    <switch name="GEARDOWN" mode="pulse">controls/gear/down</switch>
+    <magnetos start="3">
+        <target>controls/engines/engine[0]/magnetos</target>
+        <target delay="500" both="5">controls/engines/engine[1]/magnetos</target>
+    </magnetos>
+    <starter mode="pulse" duration="1000">controls/switches/starter</starter>
+    <gear-retarget invert="true">controls/gear/gear-down</gear-retarget>
+    <gear-primer strokes="3" interval="250">controls/engines/engine/primer</gear-primer>
+    <panel serial="A1234"/>
+    <gear-leds port="60014" nose="2" left="0" right="1"/>
+</plane>
+"#;
+
+    /// A configuration file supplied with the program, as built in
+    fn supplied(file: &str) -> Simulator {
+        Simulator::new(&format!("{}{}", BUILTIN_PREFIX, file)).unwrap()
+    }
+
+    /// Check that two configurations send the same to the simulator
+    fn assert_same(loaded: &Simulator, converted: &Simulator, what: &str) {
+        assert_eq!(converted.plane, loaded.plane, "{}", what);
+        assert_eq!(converted.aircraft, loaded.aircraft, "{}", what);
+        assert_eq!(converted.switch_mapper, loaded.switch_mapper, "{}", what);
+        let mut ignored = loaded.ignored.clone();
+        ignored.sort_by_key(|id| id.name());
+        assert_eq!(converted.ignored, ignored, "{}", what);
+        assert_eq!(converted.gear_mapper, loaded.gear_mapper, "{}", what);
+        assert_eq!(converted.magneto, loaded.magneto, "{}", what);
+        assert_eq!(converted.starter, loaded.starter, "{}", what);
+        assert_eq!(converted.gear_retarget, loaded.gear_retarget, "{}", what);
+        assert_eq!(converted.gear_primer, loaded.gear_primer, "{}", what);
+        assert_eq!(converted.panel, loaded.panel, "{}", what);
+        assert_eq!(converted.gear_leds, loaded.gear_leds, "{}", what);
+        assert_eq!(converted.warnings.len(), loaded.warnings.len(), "{}", what);
+    }
+
+    /// Convert to TOML and JSON, and check each loads as the same configuration
+    fn round_trip(loaded: &Simulator, name: &str) {
+        for (format, extension) in [(Format::Toml, "toml"), (Format::Json, "json")] {
+            let what = format!("{} as {}", name, extension);
+            let file = format!("{}.{}", name, extension);
+            let files = Files::new(
+                &format!("convert-{}-{}", name, extension),
+                &[(&file, &loaded.convert(format).unwrap())],
+            );
+            assert_same(loaded, &files.load(&file), &what);
+        }
+    }
+
+    #[test]
+    fn supplied_configurations_round_trip() {
+        for file in [
+            "switchdefaultconfig.xml",
+            "cessna.xml",
+            "cessna182s.xml",
+            "piper.xml",
+        ] {
+            round_trip(&supplied(file), file);
+        }
+    }
+
+    #[test]
+    fn every_element_round_trips() {
+        let files = Files::new("everything", &[("everything.xml", EVERYTHING)]);
+        let loaded = files.load("everything.xml");
+        assert_eq!(loaded.switch_mapper.len(), 4);
+        assert_eq!(loaded.gear_mapper.len(), 2);
+        assert_eq!(loaded.magneto.targets.len(), 2);
+        round_trip(&loaded, "everything");
+    }
+
+    #[test]
+    fn converted_text() {
+        let loaded = supplied("cessna.xml");
+        let toml = loaded.convert(Format::Toml).unwrap();
+        assert!(toml.starts_with("plane = \"Cessna 172P\"\naircraft = [\"c172p\"]\n"));
+        assert!(toml.contains(
+            "[switches.COWLCLOSE]\nproperty = \"controls/engines/engine/cowl-flaps-norm\"\ninvert = true\n"
+        ));
+        let json = loaded.convert(Format::Json).unwrap();
+        assert!(json.starts_with("{\n  \"plane\": \"Cessna 172P\",\n"));
+        assert!(loaded.convert(Format::Xml).is_err());
+    }
+
+    #[test]
+    fn unknown_setting_is_an_error() {
+        let files = Files::new(
+            "unknown",
+            &[(
+                "unknown.toml",
+                "plane = \"Test\"\n\n[switches.BATTERY]\nproperty = \"a/bat\"\ncolour = \"red\"\n",
+            )],
+        );
+        let errors = files.errors("unknown.toml");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].position.map(|(line, _)| line), Some(5));
+        assert!(errors[0].message.contains("unknown field `colour`"));
+    }
+}
@@ -0,0 +1,187 @@
+//!
+//! Where the configuration file is found when none is given, and the profiles built
+//! into the program.
+//!
+//! The configuration file is the first of
+//!     the file given on the command line
+//!     the file named by $SAITEKSWITCH_CONFIG
+//!     switchdefaultconfig.xml (or .toml, .json) in $XDG_CONFIG_HOME/saitekswitch/
+//!         ($XDG_CONFIG_HOME is ~/.config if not set, or %APPDATA% on Windows)
+//!     switchdefaultconfig.xml (or .toml, .json) in /etc/saitekswitch/ (not Windows)
+//!     the built in 'builtin:switchdefaultconfig.xml'
+//!
+//! The supplied configuration files are built in, and are named 'builtin:cessna.xml' etc.
+//! They are also the last profiles looked at when choosing one for the aircraft.
+//!
+
+use std::env;
+use std::path::PathBuf;
+
+/// The prefix naming a configuration file built into the program
+pub const BUILTIN_PREFIX: &str = "builtin:";
+
+const DEFAULT_CONFIG: &str = "switchdefaultconfig";
+const CONFIG_EXTENSIONS: [&str; 3] = ["xml", "toml", "json"];
+
+// the supplied configuration files, by name
+const BUILTIN_PROFILES: [(&str, &str); 4] = [
+    (
+        "switchdefaultconfig.xml",
+        include_str!("../../data/switchdefaultconfig.xml"),
+    ),
+    ("cessna.xml", include_str!("../../data/cessna.xml")),
+    ("cessna182s.xml", include_str!("../../data/cessna182s.xml")),
+    ("piper.xml", include_str!("../../data/piper.xml")),
+];
+
+/// The text of a built in configuration file, None if 'filename' is not one
+pub(crate) fn builtin_profile(filename: &str) -> Option<&'static str> {
+    let name = filename.strip_prefix(BUILTIN_PREFIX)?;
+    BUILTIN_PROFILES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, text)| *text)
+}
+
+/// The names of the built in configuration files, with the prefix
+pub(crate) fn builtin_profiles() -> impl Iterator<Item = PathBuf> {
+    BUILTIN_PROFILES
+        .iter()
+        .map(|(name, _)| PathBuf::from(format!("{}{}", BUILTIN_PREFIX, name)))
+}
+
+/// The configuration file to use, and how it was found.
+/// A file given, or named by $SAITEKSWITCH_CONFIG, is used even if it does not exist,
+/// so that the mistake is reported.
+pub fn find_config(given: Option<String>) -> (String, &'static str) {
+    if let Some(config) = given {
+        return (config, "given");
+    }
+    if let Some(config) = variable("SAITEKSWITCH_CONFIG") {
+        return (config.display().to_string(), "from $SAITEKSWITCH_CONFIG");
+    }
+    let directories = [
+        (config_home(), "from the user configuration directory"),
+        (system_config(), "from the system configuration directory"),
+    ];
+    for (directory, found) in directories {
+        let directory = match directory {
+            Some(directory) => directory.join("saitekswitch"),
+            None => continue,
+        };
+        for extension in CONFIG_EXTENSIONS {
+            let config = directory.join(format!("{}.{}", DEFAULT_CONFIG, extension));
+            if config.is_file() {
+                return (config.display().to_string(), found);
+            }
+        }
+    }
+    (
+        format!("{}{}.xml", BUILTIN_PREFIX, DEFAULT_CONFIG),
+        "built in",
+    )
+}
+
+/// An environment variable, None if it is not set or empty
+fn variable(name: &str) -> Option<PathBuf> {
+    env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// $XDG_CONFIG_HOME, or its default
+fn config_home() -> Option<PathBuf> {
+    variable("XDG_CONFIG_HOME").or_else(default_config_home)
+}
+
+#[cfg(windows)]
+fn default_config_home() -> Option<PathBuf> {
+    variable("APPDATA")
+}
+
+#[cfg(not(windows))]
+fn default_config_home() -> Option<PathBuf> {
+    variable("HOME").map(|home| home.join(".config"))
+}
+
+#[cfg(windows)]
+fn system_config() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(not(windows))]
+fn system_config() -> Option<PathBuf> {
+    Some(PathBuf::from("/etc"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::plane_aircraft;
+    use crate::testing::Files;
+    use crate::Simulator;
+
+    use std::path::Path;
+
+    #[test]
+    fn builtin_profiles_load_without_warnings() {
+        for profile in builtin_profiles() {
+            let name = profile.display().to_string();
+            let loaded = Simulator::new(&name).unwrap();
+            assert!(
+                loaded.warnings.is_empty(),
+                "{}: {:?}",
+                name,
+                loaded.warnings
+            );
+        }
+        assert_eq!(
+            plane_aircraft(Path::new("builtin:cessna182s.xml")),
+            vec!["c182s"]
+        );
+        assert!(builtin_profile("builtin:missing.xml").is_none());
+        assert!(builtin_profile("cessna.xml").is_none());
+    }
+
+    // the environment is shared by the tests, so the search order is checked in one test
+    #[test]
+    fn search_order() {
+        let files = Files::new(
+            "search",
+            &[
+                ("home/saitekswitch/switchdefaultconfig.toml", ""),
+                ("home/saitekswitch/switchdefaultconfig.json", ""),
+            ],
+        );
+        env::set_var("SAITEKSWITCH_CONFIG", files.path("variable.xml"));
+        env::set_var("XDG_CONFIG_HOME", files.path("home"));
+
+        let given = Some("given.xml".to_string());
+        assert_eq!(find_config(given), ("given.xml".to_string(), "given"));
+        // the variable's file is used even though it does not exist
+        assert_eq!(
+            find_config(None),
+            (files.path("variable.xml"), "from $SAITEKSWITCH_CONFIG")
+        );
+
+        env::set_var("SAITEKSWITCH_CONFIG", "");
+        assert_eq!(
+            find_config(None),
+            (
+                files.path("home/saitekswitch/switchdefaultconfig.toml"),
+                "from the user configuration directory"
+            )
+        );
+
+        env::set_var("XDG_CONFIG_HOME", files.path("empty"));
+        let (config, found) = find_config(None);
+        if system_config().is_none_or(|etc| !etc.join("saitekswitch").is_dir()) {
+            assert_eq!(config, "builtin:switchdefaultconfig.xml");
+            assert_eq!(found, "built in");
+        } else {
+            assert_ne!(found, "from the user configuration directory");
+        }
+        env::remove_var("SAITEKSWITCH_CONFIG");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+}
@@ -0,0 +1,228 @@
+//!
+//! Writes to the simulator which wait for their time: targets sent after a delay,
+//! the strokes of the primer, and the end of a pulse.
+//!
+//! Nothing sleeps while waiting, so the panel is still read. The writes are kept
+//! in the order they are due, and sent by 'Simulator::tick', which the driver
+//! calls at least every 100ms.
+//!
+
+use crate::{MagnetoCommand, SwitchCommand, SwitchMode, Value};
+
+use switch::MagnetoPosition;
+
+use std::time::{Duration, Instant};
+
+/// A write to a simulator property, sent when due
+#[derive(Debug, Clone, PartialEq)]
+struct Write {
+    due: Instant,
+    control: String,
+    action_type: &'static str,
+    action: String,
+    initial: bool, // an initial setting, replaced if the control is operated before it is sent
+}
+
+/// The writes waiting to be sent, in the order they are due
+#[derive(Debug, Default)]
+pub(crate) struct Schedule {
+    writes: Vec<Write>,
+    initialising: bool, // writes added are initial settings
+}
+
+impl Schedule {
+    /// Add a write, after any others due at the same time.
+    /// An initial setting for the same control which has not been sent yet is dropped,
+    /// so it does not undo the new write.
+    fn add(&mut self, due: Instant, control: &str, action_type: &'static str, action: String) {
+        if !self.initialising {
+            self.writes
+                .retain(|write| !(write.initial && write.control == control));
+        }
+        let index = self.writes.partition_point(|write| write.due <= due);
+        self.writes.insert(
+            index,
+            Write {
+                due,
+                control: control.to_string(),
+                action_type,
+                action,
+                initial: self.initialising,
+            },
+        );
+    }
+
+    /// Mark the writes added from now on as initial settings, or not
+    pub(crate) fn set_initialising(&mut self, initialising: bool) {
+        self.initialising = initialising;
+    }
+
+    fn value(&mut self, due: Instant, control: &str, value: &Value) {
+        self.add(due, control, value.type_name(), value.to_string());
+    }
+
+    /// The value for a switch setting to each of its targets in turn, from 'start'.
+    /// A pulse sends the 'on' values, and the 'off' values when the pulse is over,
+    /// if the switch setting fires it. A toggle sends the new switch setting, which
+    /// is not used by 'saitekswitch.nas'.
+    pub(crate) fn switch(&mut self, command: &SwitchCommand, on: bool, start: Instant) {
+        let mut due = start;
+        match command.mode {
+            SwitchMode::Level => {
+                for target in &command.targets {
+                    due += target.delay;
+                    self.value(due, &target.property, target.value(on));
+                }
+            }
+            SwitchMode::Pulse if command.edge.fires(on) => {
+                for target in &command.targets {
+                    due += target.delay;
+                    self.value(due, &target.property, target.value(true));
+                    self.value(
+                        due + command.duration,
+                        &target.property,
+                        target.value(false),
+                    );
+                }
+            }
+            SwitchMode::Pulse => {}
+            SwitchMode::Toggle => {
+                for target in &command.targets {
+                    due += target.delay;
+                    self.add(due, &target.property, "toggle", u8::from(on).to_string());
+                }
+            }
+        }
+    }
+
+    /// The value for a Magneto position to each of its targets in turn, from 'start'
+    pub(crate) fn magneto(
+        &mut self,
+        command: &MagnetoCommand,
+        position: MagnetoPosition,
+        start: Instant,
+    ) {
+        let mut due = start;
+        for target in &command.targets {
+            if let Some(value) = target.value(position) {
+                due += target.delay;
+                self.value(due, &target.property, value);
+            }
+        }
+    }
+
+    /// Add one to the property for each stroke, 'interval' apart
+    pub(crate) fn strokes(&mut self, control: &str, strokes: u32, interval: Duration) {
+        let start = Instant::now();
+        for stroke in 0..strokes {
+            self.add(start + interval * stroke, control, "add", "1".to_string());
+        }
+    }
+
+    /// When the next write is due, None if there are none
+    pub(crate) fn next_due(&self) -> Option<Instant> {
+        self.writes.first().map(|write| write.due)
+    }
+
+    /// Send the writes which are due with 'send', given the control, action type and action
+    pub(crate) fn send_due(&mut self, now: Instant, mut send: impl FnMut(&str, &str, &str)) {
+        let due = self.writes.partition_point(|write| write.due <= now);
+        for write in self.writes.drain(..due) {
+            send(&write.control, write.action_type, &write.action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PulseEdge, Target};
+
+    /// The writes due by 'now', as sent to the simulator
+    fn sent(schedule: &mut Schedule, now: Instant) -> Vec<String> {
+        let mut sent = Vec::new();
+        schedule.send_due(now, |control, action_type, action| {
+            sent.push(format!("{},{},{}", control, action_type, action))
+        });
+        sent
+    }
+
+    #[test]
+    fn targets_in_turn() {
+        let command = SwitchCommand {
+            targets: vec![
+                Target {
+                    on: Value::Double(0.8),
+                    ..Target::new("a/first")
+                },
+                Target {
+                    delay: Duration::from_millis(200),
+                    ..Target::new("a/second")
+                },
+                Target::new("a/third"),
+            ],
+            ..SwitchCommand::default()
+        };
+        let mut schedule = Schedule::default();
+        let start = Instant::now();
+        schedule.switch(&command, true, start);
+        assert_eq!(schedule.next_due(), Some(start));
+        assert_eq!(sent(&mut schedule, start), ["a/first,double,0.8"]);
+        let later = start + Duration::from_millis(200);
+        assert_eq!(schedule.next_due(), Some(later));
+        assert!(sent(&mut schedule, later - Duration::from_millis(1)).is_empty());
+        assert_eq!(
+            sent(&mut schedule, later),
+            ["a/second,int,1", "a/third,int,1"]
+        );
+        assert_eq!(schedule.next_due(), None);
+    }
+
+    #[test]
+    fn pulse() {
+        let command = SwitchCommand {
+            mode: SwitchMode::Pulse,
+            edge: PulseEdge::Off,
+            ..SwitchCommand::new("a/button")
+        };
+        let mut schedule = Schedule::default();
+        let start = Instant::now();
+        schedule.switch(&command, true, start);
+        assert_eq!(schedule.next_due(), None);
+        schedule.switch(&command, false, start);
+        assert_eq!(sent(&mut schedule, start), ["a/button,int,1"]);
+        let end = start + command.duration;
+        assert_eq!(schedule.next_due(), Some(end));
+        assert_eq!(sent(&mut schedule, end), ["a/button,int,0"]);
+    }
+
+    #[test]
+    fn strokes() {
+        let mut schedule = Schedule::default();
+        schedule.strokes("a/primer", 3, Duration::from_millis(400));
+        let start = schedule.next_due().unwrap();
+        assert_eq!(sent(&mut schedule, start), ["a/primer,add,1"]);
+        let end = start + Duration::from_millis(800);
+        assert_eq!(
+            sent(&mut schedule, end),
+            ["a/primer,add,1", "a/primer,add,1"]
+        );
+        assert_eq!(schedule.next_due(), None);
+    }
+
+    #[test]
+    fn unsent_initial_settings_dropped() {
+        let mut schedule = Schedule::default();
+        let start = Instant::now();
+        let later = start + Duration::from_millis(500);
+        schedule.set_initialising(true);
+        schedule.switch(&SwitchCommand::new("a/light"), true, later);
+        schedule.switch(&SwitchCommand::new("a/pump"), true, later);
+        schedule.set_initialising(false);
+        // the light is operated before its initial setting is sent
+        schedule.switch(&SwitchCommand::new("a/light"), false, start);
+        assert_eq!(sent(&mut schedule, start), ["a/light,int,0"]);
+        assert_eq!(sent(&mut schedule, later), ["a/pump,int,1"]);
+        assert_eq!(schedule.next_due(), None);
+    }
+}
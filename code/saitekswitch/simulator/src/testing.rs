@@ -0,0 +1,54 @@
+//!
+//! Support for the tests: configuration files in a temporary directory.
+//!
+
+use crate::{ConfigError, Simulator};
+
+use std::fs;
+use std::path::PathBuf;
+
+/// A directory of configuration files, removed when dropped
+pub(crate) struct Files(PathBuf);
+
+impl Files {
+    /// Write 'files', each (name, text), to a directory of their own named after 'test'.
+    /// A name may include subdirectories.
+    pub(crate) fn new(test: &str, files: &[(&str, &str)]) -> Files {
+        let directory =
+            std::env::temp_dir().join(format!("saitekswitch-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for (file, text) in files {
+            let path = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        Files(directory)
+    }
+
+    /// The path of 'file' in the directory
+    pub(crate) fn path(&self, file: &str) -> String {
+        self.0.join(file).display().to_string()
+    }
+
+    /// The simulator configured by 'file', which must load
+    pub(crate) fn load(&self, file: &str) -> Simulator {
+        match Simulator::new(&self.path(file)) {
+            Ok(simulator) => simulator,
+            Err(errors) => panic!("{} not loaded: {:?}", file, errors),
+        }
+    }
+
+    /// The errors in 'file', which must not load
+    pub(crate) fn errors(&self, file: &str) -> Vec<ConfigError> {
+        match Simulator::new(&self.path(file)) {
+            Ok(_) => panic!("{} loaded", file),
+            Err(errors) => errors,
+        }
+    }
+}
+
+impl Drop for Files {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
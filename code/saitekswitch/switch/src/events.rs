@@ -0,0 +1,347 @@
+//!
+//! Decoded Switch Panel events.
+//!
+//! Each report from the panel packs the settings of every control into 3 bytes
+//! (see 'switch_constants'). The 'PanelState' decodes a report, and successive
+//! states are compared to give a stream of 'PanelEvent's, so that no consumer
+//! needs to know the bit layout.
+//!
+
+use switch_constants::*;
+
+use std::fmt;
+use std::time::Instant;
+
+/// The 13 labelled switches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SwitchId {
+    Battery,
+    Alt,
+    Avionics,
+    FuelPump,
+    DeIce,
+    PitotHeat,
+    CowlClose,
+    PanelLight,
+    Beacon,
+    NavLights,
+    Strobe,
+    Taxi,
+    Landing,
+}
+
+impl SwitchId {
+    /// All the switches, in panel order
+    pub const ALL: [SwitchId; 13] = [
+        SwitchId::Battery,
+        SwitchId::Alt,
+        SwitchId::Avionics,
+        SwitchId::FuelPump,
+        SwitchId::DeIce,
+        SwitchId::PitotHeat,
+        SwitchId::CowlClose,
+        SwitchId::PanelLight,
+        SwitchId::Beacon,
+        SwitchId::NavLights,
+        SwitchId::Strobe,
+        SwitchId::Taxi,
+        SwitchId::Landing,
+    ];
+
+    /// The bit for this switch in a packed report
+    pub fn bit(self) -> u32 {
+        match self {
+            SwitchId::Battery => BATTERY,
+            SwitchId::Alt => ALT,
+            SwitchId::Avionics => AVIONICS,
+            SwitchId::FuelPump => FUELPUMP,
+            SwitchId::DeIce => DEICE,
+            SwitchId::PitotHeat => PITOTHEAT,
+            SwitchId::CowlClose => COWLCLOSE,
+            SwitchId::PanelLight => PANELLIGHT,
+            SwitchId::Beacon => BEACON,
+            SwitchId::NavLights => NAVLIGHTS,
+            SwitchId::Strobe => STROBE,
+            SwitchId::Taxi => TAXI,
+            SwitchId::Landing => LANDING,
+        }
+    }
+
+    /// The switch name, as used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            SwitchId::Battery => "BATTERY",
+            SwitchId::Alt => "ALT",
+            SwitchId::Avionics => "AVIONICS",
+            SwitchId::FuelPump => "FUELPUMP",
+            SwitchId::DeIce => "DEICE",
+            SwitchId::PitotHeat => "PITOTHEAT",
+            SwitchId::CowlClose => "COWLCLOSE",
+            SwitchId::PanelLight => "PANELLIGHT",
+            SwitchId::Beacon => "BEACON",
+            SwitchId::NavLights => "NAVLIGHTS",
+            SwitchId::Strobe => "STROBE",
+            SwitchId::Taxi => "TAXI",
+            SwitchId::Landing => "LANDING",
+        }
+    }
+
+    /// The switch with this configuration file name
+    pub fn from_name(name: &str) -> Option<SwitchId> {
+        SwitchId::ALL.iter().copied().find(|id| id.name() == name)
+    }
+}
+
+impl fmt::Display for SwitchId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Positions of the rotary Magneto switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MagnetoPosition {
+    Off,
+    Right,
+    Left,
+    Both,
+    Start,
+}
+
+impl MagnetoPosition {
+    /// All the positions, in order of rotation
+    pub const ALL: [MagnetoPosition; 5] = [
+        MagnetoPosition::Off,
+        MagnetoPosition::Right,
+        MagnetoPosition::Left,
+        MagnetoPosition::Both,
+        MagnetoPosition::Start,
+    ];
+
+    /// The bit for this position in a packed report
+    pub fn bit(self) -> u32 {
+        match self {
+            MagnetoPosition::Off => MAGOFF,
+            MagnetoPosition::Right => MAGR,
+            MagnetoPosition::Left => MAGL,
+            MagnetoPosition::Both => MAGBOTH,
+            MagnetoPosition::Start => MAGSTART,
+        }
+    }
+
+    /// The position name, as marked on the panel
+    pub fn name(self) -> &'static str {
+        match self {
+            MagnetoPosition::Off => "OFF",
+            MagnetoPosition::Right => "R",
+            MagnetoPosition::Left => "L",
+            MagnetoPosition::Both => "BOTH",
+            MagnetoPosition::Start => "START",
+        }
+    }
+}
+
+impl fmt::Display for MagnetoPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Positions of the gear lever
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GearPosition {
+    Up,
+    Down,
+}
+
+impl GearPosition {
+    /// The bit for this position in a packed report
+    pub fn bit(self) -> u32 {
+        match self {
+            GearPosition::Up => GEARUP,
+            GearPosition::Down => GEARDOWN,
+        }
+    }
+
+    /// The lever position as a switch name in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            GearPosition::Up => "GEARUP",
+            GearPosition::Down => "GEARDOWN",
+        }
+    }
+
+    /// The lever position with this configuration file switch name
+    pub fn from_name(name: &str) -> Option<GearPosition> {
+        [GearPosition::Up, GearPosition::Down]
+            .iter()
+            .copied()
+            .find(|position| position.name() == name)
+    }
+}
+
+impl fmt::Display for GearPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A change to one control on the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A labelled switch is now on or off
+    Switch { id: SwitchId, on: bool },
+    /// The Magneto switch has reached a position
+    Magneto(MagnetoPosition),
+    /// The gear lever has reached a position
+    GearLever(GearPosition),
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventKind::Switch { id, on } => write!(f, "{} {}", id, if *on { "on" } else { "off" }),
+            EventKind::Magneto(position) => write!(f, "MAGNETO {}", position),
+            EventKind::GearLever(position) => write!(f, "GEAR {:?}", position),
+        }
+    }
+}
+
+/// A change to one control on the panel, and when the report showing it was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelEvent {
+    pub time: Instant,
+    pub kind: EventKind,
+}
+
+//-------------------------------------------------------------------------------
+
+/// The settings of all the panel controls, decoded from one report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PanelState {
+    report: u32,
+}
+
+impl PanelState {
+    /// Decode a packed report
+    pub fn from_report(report: u32) -> PanelState {
+        PanelState { report }
+    }
+
+    /// The packed report
+    pub fn report(&self) -> u32 {
+        self.report
+    }
+
+    /// True if the switch is on
+    pub fn switch(&self, id: SwitchId) -> bool {
+        self.report & id.bit() != 0
+    }
+
+    /// The Magneto position, None while the switch is between positions
+    pub fn magneto(&self) -> Option<MagnetoPosition> {
+        MagnetoPosition::ALL
+            .iter()
+            .copied()
+            .find(|position| self.report & position.bit() != 0)
+    }
+
+    /// The gear lever position, None while the lever is moving
+    pub fn gear_lever(&self) -> Option<GearPosition> {
+        [GearPosition::Up, GearPosition::Down]
+            .iter()
+            .copied()
+            .find(|position| self.report & position.bit() != 0)
+    }
+
+    /// Events giving the complete settings: every switch, and the Magneto
+    /// and gear lever if they are in a position
+    pub fn events(&self, time: Instant) -> Vec<PanelEvent> {
+        let mut events: Vec<PanelEvent> = SwitchId::ALL
+            .iter()
+            .map(|id| PanelEvent {
+                time,
+                kind: EventKind::Switch {
+                    id: *id,
+                    on: self.switch(*id),
+                },
+            })
+            .collect();
+        if let Some(position) = self.gear_lever() {
+            events.push(PanelEvent {
+                time,
+                kind: EventKind::GearLever(position),
+            });
+        }
+        if let Some(position) = self.magneto() {
+            events.push(PanelEvent {
+                time,
+                kind: EventKind::Magneto(position),
+            });
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_report() {
+        let state = PanelState::from_report(BATTERY | LANDING | MAGR | GEARUP);
+        assert!(state.switch(SwitchId::Battery));
+        assert!(state.switch(SwitchId::Landing));
+        assert!(!state.switch(SwitchId::Alt));
+        assert_eq!(state.magneto(), Some(MagnetoPosition::Right));
+        assert_eq!(state.gear_lever(), Some(GearPosition::Up));
+    }
+
+    #[test]
+    fn between_positions() {
+        let state = PanelState::from_report(BATTERY);
+        assert_eq!(state.magneto(), None);
+        assert_eq!(state.gear_lever(), None);
+    }
+
+    #[test]
+    fn complete_settings() {
+        let time = Instant::now();
+        let events = PanelState::from_report(STROBE | MAGSTART | GEARDOWN).events(time);
+        assert_eq!(events.len(), SwitchId::ALL.len() + 2);
+        assert!(events.iter().all(|event| event.time == time));
+        assert!(events.iter().any(|event| event.kind
+            == EventKind::Switch {
+                id: SwitchId::Strobe,
+                on: true
+            }));
+        assert!(events.iter().any(|event| event.kind
+            == EventKind::Switch {
+                id: SwitchId::Beacon,
+                on: false
+            }));
+        let controls: Vec<EventKind> = events[SwitchId::ALL.len()..]
+            .iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            controls,
+            vec![
+                EventKind::GearLever(GearPosition::Down),
+                EventKind::Magneto(MagnetoPosition::Start)
+            ]
+        );
+        // the Magneto and gear lever are left out between positions
+        let events = PanelState::from_report(0).events(time);
+        assert_eq!(events.len(), SwitchId::ALL.len());
+    }
+
+    #[test]
+    fn names() {
+        for id in SwitchId::ALL {
+            assert_eq!(SwitchId::from_name(id.name()), Some(id));
+        }
+        assert_eq!(GearPosition::from_name("GEARUP"), Some(GearPosition::Up));
+        assert_eq!(SwitchId::from_name("MAGOFF"), None);
+    }
+}
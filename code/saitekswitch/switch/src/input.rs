@@ -0,0 +1,536 @@
+//!
+//! Input sources for the Saitek Switch Panel.
+//!
+//! Every source delivers the same raw reports as the real panel: 3 data bytes
+//! (plus one unused byte for Windows hidapi) per report.
+//!   hid         - the Switch Panel itself, located by vendor and device ID, and
+//!                 optionally by serial number or HID path when several are attached
+//!   pipe        - the Switch Panel Emulator piped into STDIN
+//!   file:<path> - a file of captured reports, read once from start to end
+//!   replay:<path>      - a recording (see 'record.rs'), with the original timing
+//!   replay-fast:<path> - a recording, as fast as possible
+//!   socket:<address>   - the Switch Panel Emulator, over a local TCP connection
+//!   emulator           - the Switch Panel Emulator at its usual address
+//!
+//! Over a socket, the emulator sends 4-byte reports, and is sent each 2-byte LED
+//! feature report in return.
+//!
+
+use crate::record::ReplayInput;
+use crate::GearLeds;
+
+use hidapi::{HidApi, HidDevice};
+
+use std::ffi::CString;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Saitek is 6a3, switch is d67, radio is d08
+const VENDOR_ID: u16 = 0x06a3;
+const SWITCH_ID: u16 = 0x0d67;
+
+/// Address on which the Switch Panel Emulator accepts a connection
+pub const EMULATOR_ADDRESS: &str = "127.0.0.1:60010";
+
+// Interval between attempts to reopen a disconnected Switch Panel
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Bytes read from STDIN at a time, one report from the emulator
+const PIPE_REPORT_SIZE: usize = 4;
+
+/// A source of switch panel reports
+pub trait InputSource {
+    /// Blocking read of one report into 'buf'.
+    /// Returns the number of bytes read, 0 when the source has no more data.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Read of one report into 'buf', waiting no longer than 'timeout'.
+    /// Returns None if no report arrived in time.
+    /// Sources which cannot time out make a blocking read.
+    fn read_timeout(&mut self, buf: &mut [u8], _timeout: Duration) -> io::Result<Option<usize>> {
+        self.read(buf).map(Some)
+    }
+
+    /// When the report last read arrived, for sources which read ahead so that a
+    /// report may wait before it is read. None if it arrived as it was read.
+    fn arrived(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Send a feature report (the gear LEDs) to the panel.
+    /// Sources other than the real panel have no LEDs, and ignore this.
+    fn send_feature_report(&mut self, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// True if the source can be reopened after a read error, e.g. the panel
+    /// being unplugged and plugged back in.
+    fn can_reconnect(&self) -> bool {
+        false
+    }
+
+    /// Wait until the source can be reopened, and reopen it.
+    fn reconnect(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "input source cannot be reconnected",
+        ))
+    }
+}
+
+/// Selects which Switch Panel to open when several are attached to the host
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PanelSelector {
+    /// The first panel found
+    #[default]
+    First,
+    /// The panel with this serial number
+    Serial(String),
+    /// The panel at this HID path (e.g. /dev/hidraw3 or a libusb path such as 1-2:1.0)
+    Path(String),
+}
+
+impl fmt::Display for PanelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PanelSelector::First => write!(f, "first panel"),
+            PanelSelector::Serial(serial) => write!(f, "serial number {}", serial),
+            PanelSelector::Path(path) => write!(f, "path {}", path),
+        }
+    }
+}
+
+/// Details of an attached Switch Panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanelInfo {
+    pub path: String,
+    pub serial: Option<String>,
+}
+
+/// The kind of input source, as selected on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputKind {
+    Hid(PanelSelector),
+    Pipe,
+    File(String),
+    Replay { path: String, fast: bool },
+    Socket(String),
+}
+
+impl InputKind {
+    /// Open the input source of this kind
+    pub fn open(&self) -> io::Result<Box<dyn InputSource>> {
+        Ok(match self {
+            InputKind::Hid(selector) => Box::new(HidInput::open(selector.clone())?),
+            InputKind::Pipe => Box::new(PipeInput::new()),
+            InputKind::File(path) => Box::new(FileInput::open(path)?),
+            InputKind::Replay { path, fast } => Box::new(ReplayInput::open(path, *fast)?),
+            InputKind::Socket(address) => Box::new(SocketInput::connect(address)?),
+        })
+    }
+}
+
+impl FromStr for InputKind {
+    type Err = String;
+
+    /// Parse 'hid', 'pipe', 'file:<path>', 'replay:<path>', 'replay-fast:<path>',
+    /// 'socket:<address>' or 'emulator'
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, path) = s.split_once(':').unwrap_or((s, ""));
+        match (kind, path.is_empty()) {
+            ("hid", true) => Ok(InputKind::Hid(PanelSelector::First)),
+            ("pipe", true) => Ok(InputKind::Pipe),
+            ("file", false) => Ok(InputKind::File(path.to_string())),
+            ("replay", false) | ("replay-fast", false) => Ok(InputKind::Replay {
+                path: path.to_string(),
+                fast: kind == "replay-fast",
+            }),
+            ("socket", false) => Ok(InputKind::Socket(path.to_string())),
+            ("emulator", true) => Ok(InputKind::Socket(EMULATOR_ADDRESS.to_string())),
+            _ => Err(format!(
+                "Unknown input '{}', expected hid, pipe, file:<path>, replay:<path>, \
+                 replay-fast:<path>, socket:<address> or emulator",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for InputKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputKind::Hid(PanelSelector::First) => write!(f, "hid"),
+            InputKind::Hid(selector) => write!(f, "hid {}", selector),
+            InputKind::Pipe => write!(f, "pipe"),
+            InputKind::File(path) => write!(f, "file:{}", path),
+            InputKind::Replay { path, fast: false } => write!(f, "replay:{}", path),
+            InputKind::Replay { path, fast: true } => write!(f, "replay-fast:{}", path),
+            InputKind::Socket(address) => write!(f, "socket:{}", address),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------
+
+/// The Saitek Switch Panel as a HID device
+pub struct HidInput {
+    api: HidApi,
+    device: HidDevice,
+    selector: PanelSelector, // used again when reconnecting
+}
+
+impl HidInput {
+    /// Open the Switch Panel located by vendor and device ID, and by 'selector'
+    /// when several are attached.
+    /// The device is always a blocking read.
+    pub fn open(selector: PanelSelector) -> io::Result<HidInput> {
+        let ctxt = HidApi::new().map_err(hid_error)?;
+        let device = Self::open_selected(&ctxt, &selector).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Saitek Switch not found ({}): {}", selector, e),
+            )
+        })?;
+        Ok(HidInput {
+            api: ctxt,
+            device,
+            selector,
+        })
+    }
+
+    /// List the attached Switch Panels
+    pub fn list() -> io::Result<Vec<PanelInfo>> {
+        let ctxt = HidApi::new().map_err(hid_error)?;
+        Ok(Self::panels(&ctxt))
+    }
+
+    /// The Switch Panels known to 'api', from its last enumeration
+    fn panels(api: &HidApi) -> Vec<PanelInfo> {
+        api.device_list()
+            .filter(|info| info.vendor_id() == VENDOR_ID && info.product_id() == SWITCH_ID)
+            .map(|info| PanelInfo {
+                path: info.path().to_string_lossy().into_owned(),
+                serial: info.serial_number().map(|s| s.to_string()),
+            })
+            .collect()
+    }
+
+    /// Open the selected Switch Panel. A HID path must belong to a Switch Panel.
+    fn open_selected(api: &HidApi, selector: &PanelSelector) -> io::Result<HidDevice> {
+        match selector {
+            PanelSelector::First => api.open(VENDOR_ID, SWITCH_ID).map_err(hid_error),
+            PanelSelector::Serial(serial) => api
+                .open_serial(VENDOR_ID, SWITCH_ID, serial)
+                .map_err(hid_error),
+            PanelSelector::Path(path) => {
+                if !Self::panels(api).iter().any(|panel| panel.path == *path) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "not one of the attached panels",
+                    ));
+                }
+                let path = CString::new(path.as_str())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                api.open_path(&path).map_err(hid_error)
+            }
+        }
+    }
+}
+
+impl InputSource for HidInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.device.read(buf).map_err(hid_error)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<Option<usize>> {
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        match self.device.read_timeout(buf, millis).map_err(hid_error)? {
+            0 => Ok(None),
+            length => Ok(Some(length)),
+        }
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> io::Result<()> {
+        self.device.send_feature_report(data).map_err(hid_error)
+    }
+
+    fn can_reconnect(&self) -> bool {
+        true
+    }
+
+    /// Poll for the selected Switch Panel until it can be opened again
+    fn reconnect(&mut self) -> io::Result<()> {
+        loop {
+            thread::sleep(RECONNECT_INTERVAL);
+            self.api.refresh_devices().map_err(hid_error)?;
+            if let Ok(device) = Self::open_selected(&self.api, &self.selector) {
+                self.device = device;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Convert a hidapi error for reporting as an io::Error
+fn hid_error(err: hidapi::HidError) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+//-------------------------------------------------------------------------------
+
+/// Reports piped into STDIN from the Switch Panel Emulator
+pub struct PipeInput {
+    reports: Receiver<(Instant, io::Result<Vec<u8>>)>, // from the thread reading STDIN
+    arrived: Option<Instant>,                          // when the last report was read from STDIN
+}
+
+impl PipeInput {
+    /// Start a thread reading STDIN, so that reads can time out
+    pub fn new() -> PipeInput {
+        let (sender, reports) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = io::stdin();
+            loop {
+                let mut buf = [0u8; PIPE_REPORT_SIZE];
+                let result = stdin.read(&mut buf).map(|length| buf[..length].to_vec());
+                let ended = !matches!(&result, Ok(data) if !data.is_empty());
+                if sender.send((Instant::now(), result)).is_err() || ended {
+                    return;
+                }
+            }
+        });
+        PipeInput {
+            reports,
+            arrived: None,
+        }
+    }
+
+    /// Copy a report from the reading thread into 'buf'.
+    /// The thread has gone when STDIN has ended.
+    fn report(
+        &mut self,
+        result: Result<(Instant, io::Result<Vec<u8>>), RecvTimeoutError>,
+        buf: &mut [u8],
+    ) -> io::Result<Option<usize>> {
+        match result {
+            Ok((arrived, data)) => {
+                self.arrived = Some(arrived);
+                let data = data?;
+                let length = data.len().min(buf.len());
+                buf[..length].copy_from_slice(&data[..length]);
+                Ok(Some(length))
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Ok(Some(0)),
+        }
+    }
+}
+
+impl Default for PipeInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for PipeInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self
+            .reports
+            .recv()
+            .map_err(|_| RecvTimeoutError::Disconnected);
+        self.report(result, buf).map(|length| length.unwrap_or(0))
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<Option<usize>> {
+        let result = self.reports.recv_timeout(timeout);
+        self.report(result, buf)
+    }
+
+    fn arrived(&self) -> Option<Instant> {
+        self.arrived
+    }
+
+    /// The emulator has no LEDs, so log the setting instead
+    fn send_feature_report(&mut self, data: &[u8]) -> io::Result<()> {
+        if let Some(code) = data.get(1) {
+            println!("Gear LEDs {}", GearLeds::decode(*code));
+        }
+        Ok(())
+    }
+}
+
+//-------------------------------------------------------------------------------
+
+/// Reports captured in a file, one fixed-size record per report
+pub struct FileInput {
+    file: BufReader<File>,
+}
+
+impl FileInput {
+    pub fn open(path: &str) -> io::Result<FileInput> {
+        let file = File::open(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Unable to open input file '{}': {}", path, e),
+            )
+        })?;
+        Ok(FileInput {
+            file: BufReader::new(file),
+        })
+    }
+}
+
+impl InputSource for FileInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // a short record at the end of the file is treated as end of data
+        match self.file.read_exact(buf) {
+            Ok(()) => Ok(buf.len()),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+//-------------------------------------------------------------------------------
+
+/// The Switch Panel Emulator, connected over a local TCP socket
+pub struct SocketInput {
+    stream: TcpStream,
+    pending: Vec<u8>, // bytes received, not yet a complete report
+}
+
+impl SocketInput {
+    pub fn connect(address: &str) -> io::Result<SocketInput> {
+        let stream = TcpStream::connect(address).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Unable to connect to emulator at {}: {}", address, e),
+            )
+        })?;
+        Ok(SocketInput {
+            stream,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Fill 'buf' with the next report, reading as necessary.
+    /// Returns None if the socket read times out first.
+    fn next_report(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        while self.pending.len() < buf.len() {
+            let mut chunk = [0u8; 64];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(Some(0)), // emulator has gone
+                Ok(length) => self.pending.extend_from_slice(&chunk[..length]),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        buf.copy_from_slice(&self.pending[..buf.len()]);
+        self.pending.drain(..buf.len());
+        Ok(Some(buf.len()))
+    }
+}
+
+impl InputSource for SocketInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(None)?;
+        Ok(self.next_report(buf)?.unwrap_or(0))
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<Option<usize>> {
+        // a zero timeout is not allowed on a socket
+        let timeout = timeout.max(Duration::from_millis(1));
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.next_report(buf)
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> io::Result<()> {
+        self.stream.write_all(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_kinds() {
+        let kind = |s: &str| s.parse::<InputKind>();
+        assert_eq!(kind("hid"), Ok(InputKind::Hid(PanelSelector::First)));
+        assert_eq!(kind("pipe"), Ok(InputKind::Pipe));
+        assert_eq!(
+            kind("file:capture.bin"),
+            Ok(InputKind::File("capture.bin".to_string()))
+        );
+        assert_eq!(
+            kind("replay:session.txt"),
+            Ok(InputKind::Replay {
+                path: "session.txt".to_string(),
+                fast: false
+            })
+        );
+        assert_eq!(
+            kind("replay-fast:session.txt"),
+            Ok(InputKind::Replay {
+                path: "session.txt".to_string(),
+                fast: true
+            })
+        );
+        assert_eq!(
+            kind("socket:127.0.0.1:60011"),
+            Ok(InputKind::Socket("127.0.0.1:60011".to_string()))
+        );
+        assert_eq!(
+            kind("emulator"),
+            Ok(InputKind::Socket(EMULATOR_ADDRESS.to_string()))
+        );
+    }
+
+    #[test]
+    fn unknown_input_kinds() {
+        for s in [
+            "",
+            "usb",
+            "HID",
+            "hid:1-2:1.0",
+            "pipe:x",
+            "file",
+            "file:",
+            "replay",
+            "socket:",
+            "emulator:60010",
+        ] {
+            let error = s.parse::<InputKind>().unwrap_err();
+            assert!(
+                error.starts_with(&format!("Unknown input '{}'", s)),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn display_parses_back() {
+        for s in [
+            "hid",
+            "pipe",
+            "file:a.bin",
+            "replay:a.txt",
+            "replay-fast:a.txt",
+            "socket:localhost:1",
+        ] {
+            assert_eq!(s.parse::<InputKind>().unwrap().to_string(), s);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//!
+//! The three landing gear LEDs on the Switch Panel: nose, left and right.
+//! Each LED has a red and a green element; with both lit the LED shows yellow.
+//! The LEDs are written as a single feature report byte (see 'SaitekProtocol').
+//!
+
+use switch_constants::*;
+
+use std::fmt;
+
+/// The colour shown by one gear LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LedColour {
+    #[default]
+    Off,
+    Green,
+    Red,
+    Yellow,
+}
+
+impl LedColour {
+    /// Encode using the red and green bits for one LED
+    fn encode(self, red: u8, green: u8) -> u8 {
+        match self {
+            LedColour::Off => 0,
+            LedColour::Green => green,
+            LedColour::Red => red,
+            LedColour::Yellow => red | green,
+        }
+    }
+
+    /// Decode from the red and green bits for one LED
+    fn decode(code: u8, red: u8, green: u8) -> LedColour {
+        match (code & red != 0, code & green != 0) {
+            (false, false) => LedColour::Off,
+            (false, true) => LedColour::Green,
+            (true, false) => LedColour::Red,
+            (true, true) => LedColour::Yellow,
+        }
+    }
+}
+
+impl fmt::Display for LedColour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LedColour::Off => "off",
+            LedColour::Green => "green",
+            LedColour::Red => "red",
+            LedColour::Yellow => "yellow",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The setting of all three gear LEDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GearLeds {
+    pub nose: LedColour,
+    pub left: LedColour,
+    pub right: LedColour,
+}
+
+impl GearLeds {
+    /// All LEDs dark
+    pub const OFF: GearLeds = GearLeds {
+        nose: LedColour::Off,
+        left: LedColour::Off,
+        right: LedColour::Off,
+    };
+
+    /// All three LEDs showing the same colour
+    pub fn all(colour: LedColour) -> GearLeds {
+        GearLeds {
+            nose: colour,
+            left: colour,
+            right: colour,
+        }
+    }
+
+    /// Encode as the feature report byte for the Switch Panel
+    pub fn encode(&self) -> u8 {
+        self.nose.encode(NOSERED, NOSEGREEN)
+            | self.left.encode(LEFTRED, LEFTGREEN)
+            | self.right.encode(RIGHTRED, RIGHTGREEN)
+    }
+
+    /// Decode from a feature report byte
+    pub fn decode(code: u8) -> GearLeds {
+        GearLeds {
+            nose: LedColour::decode(code, NOSERED, NOSEGREEN),
+            left: LedColour::decode(code, LEFTRED, LEFTGREEN),
+            right: LedColour::decode(code, RIGHTRED, RIGHTGREEN),
+        }
+    }
+}
+
+impl fmt::Display for GearLeds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "nose {}, left {}, right {}",
+            self.nose, self.left, self.right
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOURS: [LedColour; 4] = [
+        LedColour::Off,
+        LedColour::Green,
+        LedColour::Red,
+        LedColour::Yellow,
+    ];
+
+    #[test]
+    fn bit_patterns() {
+        assert_eq!(GearLeds::OFF.encode(), 0x00);
+        assert_eq!(GearLeds::all(LedColour::Green).encode(), 0x07);
+        assert_eq!(GearLeds::all(LedColour::Red).encode(), 0x38);
+        assert_eq!(GearLeds::all(LedColour::Yellow).encode(), 0x3f);
+        let nose_red = GearLeds {
+            nose: LedColour::Red,
+            ..GearLeds::OFF
+        };
+        assert_eq!(nose_red.encode(), NOSERED);
+        let mixed = GearLeds {
+            nose: LedColour::Green,
+            left: LedColour::Red,
+            right: LedColour::Yellow,
+        };
+        assert_eq!(mixed.encode(), NOSEGREEN | LEFTRED | RIGHTRED | RIGHTGREEN);
+    }
+
+    #[test]
+    fn decode_every_setting() {
+        for nose in COLOURS {
+            for left in COLOURS {
+                for right in COLOURS {
+                    let leds = GearLeds { nose, left, right };
+                    assert_eq!(GearLeds::decode(leds.encode()), leds);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decode_ignores_unused_bits() {
+        assert_eq!(GearLeds::decode(0xc0), GearLeds::OFF);
+        assert_eq!(GearLeds::decode(0xc1).nose, LedColour::Green);
+    }
+}
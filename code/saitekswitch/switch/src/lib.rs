@@ -30,10 +30,11 @@ THE SOFTWARE.
 //!      BATTERY, ALT, AVIONICS,FUELPUMP, DEICE, PITOTHEAT, COWLCLOSE, PANELLIGHT,
 //!      BEACON, NAVLIGHTS, STROBE, TAXI, LANDING
 //!   rotary Magneto switch
-//!
 //!      MAGOFF, MAGR, MAGL, MAGBOTH, MAGSTART
 //!   a gear lever, up or down
-//!   3 leds (R/O/G) for status indication
+//!   3 leds (R/O/G) for status indication, set with 'Device::set_leds'
+//!
+//!  Reports read from the device are decoded into a stream of 'PanelEvent's (see 'events.rs').
 //!
 //!  Any switch will trigger one or two outputs -
 //!     for switches, one output (SET or RESET)
@@ -46,199 +47,371 @@ THE SOFTWARE.
 //!   without this affecting the Linux code.
 //!
 
-use switch_constants::*;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use std::collections::HashMap;
+mod events;
+mod input;
+mod leds;
+mod record;
 
-// If the symbol 'piped' is defined, code is generated to access switch input data
-//   from an emulator piped into it.
-// Otherwise, the switch is connected as a hid device.
-// Hence '#[cfg(piped)]' will produce code for the emeulated version,
-//   and '#[cfg(not(piped))] will produce the standard production code.
-// The author has an emulator, written in C++ using the 'wxWidgets' framework that
-//   is available on request
-#[cfg(not(piped))]
-use hidapi::{HidApi, HidDevice};
+pub use events::{EventKind, GearPosition, MagnetoPosition, PanelEvent, PanelState, SwitchId};
+pub use leds::{GearLeds, LedColour};
+pub use record::{Recorder, ReplayInput};
 
-#[cfg(not(piped))]
-use std::process;
+pub use input::{
+    FileInput, HidInput, InputKind, InputSource, PanelInfo, PanelSelector, PipeInput, SocketInput,
+    EMULATOR_ADDRESS,
+};
 
-// Saitek is 6a3, switch is d67, radio is d08
-#[cfg(not(piped))]
-const VENDOR_ID: u16 = 0x06a3;
-#[cfg(not(piped))]
-const SWITCH_ID: u16 = 0x0d67;
+// The input source is chosen at startup (see 'input.rs'):
+//   the Switch Panel as a hid device, the Switch Panel Emulator over a local socket
+//   or piped into STDIN, a file of captured reports, or a recording made with
+//   'Device::start_recording'.
+// The emulator is the 'saitekswitch-emulator' program in this workspace.
 
 const RIGHT_SIZE: usize = 4; // 1 byte at end unused, required on Windows hidapi
 
-//-------------------------------------------------------------------------------
-#[cfg(not(piped))]
-pub struct Device {
-    device: HidDevice,  // for device reads and writes
-    input_current: u32, // data from device
-    input_old: u32,     // previous data
+// Longest wait for a report before any LED setting requested through a
+// 'LedControl' is sent to the device
+const LED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Outcome of a successful 'Device::read' or 'Device::read_timeout'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadStatus {
+    /// A report was read from the device, with the events for the controls
+    /// it changed (none if nothing changed)
+    Report(Vec<PanelEvent>),
+    /// The device was disconnected and has been reopened. The events give the
+    /// complete switch settings read after reconnection.
+    Reconnected(Vec<PanelEvent>),
 }
 
-#[cfg(piped)]
-use std::io::{self, Read};
-
-// if data is piped to this driver, input is via STDIN, so there is no need to
-// hold any device information
-#[cfg(piped)]
-pub struct Device {
-    input_current: u32, // data from device
-    input_old: u32,     // previous data
+/// Handle for setting the gear LEDs from another thread while the device is
+/// being read. The setting is sent to the device from within 'Device::read'.
+#[derive(Clone, Default)]
+pub struct LedControl {
+    request: Arc<Mutex<Option<GearLeds>>>,
 }
 
-impl Default for Device {
-    fn default() -> Self {
-        Self::new()
+impl LedControl {
+    /// Request a new setting for the gear LEDs, replacing any not yet sent
+    pub fn set(&self, leds: GearLeds) {
+        *self.request.lock().unwrap() = Some(leds);
+    }
+
+    /// Take the outstanding request, if any
+    fn take(&self) -> Option<GearLeds> {
+        self.request.lock().unwrap().take()
     }
 }
 
+//-------------------------------------------------------------------------------
+pub struct Device {
+    source: Box<dyn InputSource>,             // for device reads and writes
+    state: PanelState,                        // data from device
+    magneto: Option<MagnetoPosition>,         // last position reached by the Magneto switch
+    gear: Option<GearPosition>,               // and by the gear lever
+    leds: GearLeds,                           // last setting sent to the gear LEDs
+    led_control: LedControl,                  // LED settings requested from other threads
+    subscribers: Vec<Sender<PanelEvent>>,     // other consumers of the events
+    recorder: Option<Recorder>,               // saves every report, if recording
+    debounce: Duration,                       // time for a report to settle
+    unsettled: Option<(PanelState, Instant)>, // last report and when it arrived, if not yet settled
+}
+
 impl Device {
-    /// Create an instance of the Saitek Switch device.
-    /// The (first) device is located by vendor and device ID.
+    /// Create an instance of the Saitek Switch device reading from 'source'.
     /// The device is initialised and set for blocking reads
-    #[cfg(not(piped))]
-    pub fn new() -> Device {
-        let ctxt = HidApi::new().unwrap();
-
+    pub fn new(source: Box<dyn InputSource>) -> io::Result<Device> {
         let mut r = Device {
-            // ctxt: HidApi::new().unwrap(),
-            device: ctxt.open(VENDOR_ID, SWITCH_ID).unwrap_or_else(|_err| {
-                println!("Saitek Switch not found");
-                process::exit(1);
-            }),
-            input_current: 0, // adjusted during initialise_device
-            input_old: 0,
+            source,
+            state: PanelState::default(), // adjusted during initialise_device
+            magneto: None,
+            gear: None,
+            leds: GearLeds::OFF,
+            led_control: LedControl::default(),
+            subscribers: Vec::new(),
+            recorder: None,
+            debounce: Duration::ZERO,
+            unsettled: None,
         };
 
         // set up display & read selections, device is always a blocking read
-        r.input_current = Self::initialise_device(&r.device);
+        let report = r.initialise_device()?;
+        r.set_state(report);
 
-        r
+        Ok(r)
     }
 
-    /// Create an instance of the Saitek Switch device as piped data
-    /// from the switch panel emulator
-    /// The device is initialised, and set for blocking reads
-    #[cfg(piped)]
-    pub fn new() -> Device {
-        let mut r = Device {
-            input_current: 0, // adjusted during initialise_device
-            input_old: 0,
-        };
-
-        // set up display & read selections, device is always a blocking read
-        r.input_current = Self::initialise_device();
-
-        r
+    /// Blocking read of the device, decoding the report into events for the controls
+    /// which have changed. As 'read_timeout', waiting as long as needed.
+    pub fn read(&mut self) -> io::Result<ReadStatus> {
+        loop {
+            if let Some(status) = self.read_timeout(Duration::from_secs(1))? {
+                return Ok(status);
+            }
+        }
     }
 
-    /// Blocking read of the device into the 'input_current' field in the Device
-    /// struct. If there is no data, does not disturb the 'input_current' field.
+    /// Read of the device waiting no longer than 'timeout', decoding the report into
+    /// events for the controls which have changed. The events are also sent to every subscriber.
+    /// Returns None if nothing was decoded in time, so that the caller can do other work.
     /// Three data bytes are provided by the switch panel and are packed into a u32 such that
     /// the bit positions and other masks in 'switch_constants.rs' coincide.
-    #[cfg(not(piped))]
-    pub fn read(&mut self) {
-        // Non-blocking read the radio panel switches and selectors
-        // return 0 if no data, else pack 3 bytes into ls part of u32
+    /// With a debounce window, a report is only decoded once no further report has arrived
+    /// for the window, so that contact bounce and a Magneto switch passing through
+    /// positions give a single event for the settled level of each control.
+    /// If the device is disconnected, waits for it to return and reinitialises it.
+    /// While waiting for a report, LED settings requested through 'LedControl' are sent.
+    /// Returns an 'UnexpectedEof' error when the source has no more data.
+    pub fn read_timeout(&mut self, timeout: Duration) -> io::Result<Option<ReadStatus>> {
+        let deadline = Instant::now() + timeout;
         let mut buf = [0u8; RIGHT_SIZE];
-        let read_length = self.device.read(&mut buf).unwrap_or(0);
-        // device sends RIGHT_SIZE bytes, 0 on error
-        if read_length > 2 {
-            self.input_current = Self::pack(&buf);
-            // println!("Read {:6x}", self.input_current);
+        loop {
+            // wait no longer than the caller allows, or the rest of the debounce window
+            let now = Instant::now();
+            let mut wait = deadline
+                .saturating_duration_since(now)
+                .min(LED_POLL_INTERVAL);
+            if let Some((_, time)) = self.unsettled {
+                wait = wait.min((time + self.debounce).saturating_duration_since(now));
+            }
+            let result = self
+                .send_requested_leds()
+                .and_then(|_| self.source.read_timeout(&mut buf, wait));
+            let read_length = match result {
+                Ok(Some(length)) => length,
+                Ok(None) => {
+                    // no report yet, has the last one settled?
+                    match self.unsettled {
+                        Some((_, time)) if time.elapsed() >= self.debounce => {
+                            return Ok(Some(ReadStatus::Report(self.settle())))
+                        }
+                        _ if Instant::now() >= deadline => return Ok(None),
+                        _ => continue,
+                    }
+                }
+                Err(e) if self.source.can_reconnect() => {
+                    self.unsettled = None;
+                    self.reopen(e)?;
+                    let events = self.state_events();
+                    self.publish(&events);
+                    return Ok(Some(ReadStatus::Reconnected(events)));
+                }
+                Err(e) => return Err(e),
+            };
+            if read_length == 0 {
+                // the last report has settled, if there is one
+                if self.unsettled.is_some() {
+                    return Ok(Some(ReadStatus::Report(self.settle())));
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "end of Saitek Switch input",
+                ));
+            }
+            // device sends RIGHT_SIZE bytes
+            if read_length > 2 {
+                let report = Self::pack(&buf);
+                // the report may have waited to be read, e.g. while the simulator was set up
+                let arrived = self.source.arrived().unwrap_or_else(Instant::now);
+                self.record(report, arrived);
+                // println!("Read 0x{:06x} previous 0x{:06x}", report, self.state.report());
+                self.unsettled = Some((PanelState::from_report(report), arrived));
+                if self.debounce.is_zero() {
+                    return Ok(Some(ReadStatus::Report(self.settle())));
+                }
+            }
         }
     }
 
-    /// Blocking read of the device into the 'input_current' field in the Device
-    /// struct.
-    /// Three data bytes are provided by the switch panel and are packed into a u32 such that
-    /// the bit positions and other masks in 'switch_constants.rs' coincide.
-    #[cfg(piped)]
-    pub fn read(&mut self) {
-        // println!("Read - enter");
-        // blocking read the radio panel switches and selectors
-        // return 0 if no data, else pack 3 bytes into ls part of u32
-        let mut buf = [0u8; RIGHT_SIZE];
-        let read_length = match io::stdin().read(&mut buf) {
-            Ok(l) => l,  // good read, should be 4
-            Err(_) => 0, // probably non-blocking - no data
-        };
-        // println!("Read complete - {} bytes read", read_length);
-        // device sends RIGHT_SIZE bytes, 0 on error
-        if read_length > 2 {
-            self.input_current = Self::pack(&buf);
-            // println!("Read 0x{:06x} previous 0x{:06x}", self.input_current, self.input_old);
+    /// Takes the last report as the settled settings, and returns the events
+    /// for the changes, also sending them to the subscribers
+    fn settle(&mut self) -> Vec<PanelEvent> {
+        let mut events = Vec::new();
+        if let Some((state, time)) = self.unsettled.take() {
+            let previous = self.state;
+            self.state = state;
+            events = self.changes(&previous, time);
+            self.publish(&events);
+        }
+        events
+    }
+
+    /// Sets the debounce window: the time for which the panel must send no further
+    /// report before a report is decoded. Zero (the default) decodes every report.
+    pub fn set_debounce(&mut self, window: Duration) {
+        self.debounce = window;
+    }
+
+    /// Waits for a disconnected device to return, then reinitialises it
+    /// to obtain the complete switch settings.
+    fn reopen(&mut self, mut err: io::Error) -> io::Result<()> {
+        let leds = self.leds;
+        loop {
+            println!(
+                "Saitek Switch disconnected ({}), waiting for it to return",
+                err
+            );
+            self.source.reconnect()?;
+            println!("Saitek Switch reconnected");
+            match self.initialise_device() {
+                Ok(report) => {
+                    self.set_state(report);
+                    self.record(report, Instant::now());
+                    // restore the LEDs, unless a new setting is waiting
+                    let requested = self.led_control.take();
+                    self.led_control.set(requested.unwrap_or(leds));
+                    return Ok(());
+                }
+                Err(e) => err = e,
+            }
         }
     }
 
     //-----------------------------------------------------------------------------------------
 
-    /// Returns the current input value
+    /// Returns the current input value, as packed from the last report
     pub fn get_current_input(&self) -> u32 {
-        self.input_current
+        self.state.report()
+    }
+
+    /// Returns the current settings of the panel controls
+    pub fn get_state(&self) -> PanelState {
+        self.state
+    }
+
+    /// Returns events giving the complete current settings of the panel controls
+    pub fn state_events(&self) -> Vec<PanelEvent> {
+        self.state.events(Instant::now())
+    }
+
+    /// Returns a receiver for every event subsequently decoded by 'read'
+    pub fn subscribe(&mut self) -> Receiver<PanelEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Saves every subsequent report in a recording file, starting with the current settings
+    pub fn start_recording(&mut self, path: &str) -> io::Result<()> {
+        let mut recorder = Recorder::create(path)?;
+        recorder.record(self.state.report(), Instant::now())?;
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    /// Saves a report which arrived at 'time' in the recording file, if recording.
+    /// Recording stops if the file cannot be written.
+    fn record(&mut self, report: u32, time: Instant) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record(report, time) {
+                println!("Recording stopped: {}", e);
+                self.recorder = None;
+            }
+        }
     }
 
-    /// Returns the old input value
-    pub fn get_previous_input(&self) -> u32 {
-        self.input_old
+    /// Takes a new report as the complete settings, e.g. from initialisation
+    fn set_state(&mut self, report: u32) {
+        self.state = PanelState::from_report(report);
+        self.magneto = self.state.magneto();
+        self.gear = self.state.gear_lever();
     }
 
-    /// Saves the current input as the old input
-    pub fn preserve_current_input(&mut self) {
-        self.input_old = self.input_current;
+    /// Events for the changes from the 'previous' settings. The Magneto switch and the
+    /// gear lever are reported when they reach a new position, and ignored in between.
+    fn changes(&mut self, previous: &PanelState, time: Instant) -> Vec<PanelEvent> {
+        let mut events: Vec<PanelEvent> = SwitchId::ALL
+            .iter()
+            .filter(|id| self.state.switch(**id) != previous.switch(**id))
+            .map(|id| PanelEvent {
+                time,
+                kind: EventKind::Switch {
+                    id: *id,
+                    on: self.state.switch(*id),
+                },
+            })
+            .collect();
+        if let Some(position) = self.state.gear_lever() {
+            if self.gear != Some(position) {
+                self.gear = Some(position);
+                events.push(PanelEvent {
+                    time,
+                    kind: EventKind::GearLever(position),
+                });
+            }
+        }
+        if let Some(position) = self.state.magneto() {
+            if self.magneto != Some(position) {
+                self.magneto = Some(position);
+                events.push(PanelEvent {
+                    time,
+                    kind: EventKind::Magneto(position),
+                });
+            }
+        }
+        events
     }
 
-    /// Compares the current input  with the old input.
-    /// Returns true if they differ.
-    pub fn has_input_changed(&self) -> bool {
-        self.input_current != self.input_old
+    /// Sends events to the subscribers, forgetting any which have gone away
+    fn publish(&mut self, events: &[PanelEvent]) {
+        self.subscribers
+            .retain(|subscriber| events.iter().all(|event| subscriber.send(*event).is_ok()));
     }
 
     //----------------------------------------------------------------------------------------
 
-    /// Sends a RED led to the nosewheel, waiting for a key to be pressed.
-    /// When key is received, clears the led, and returns the
-    /// value read from the device, which gives the current switch settings.
-    #[cfg(not(piped))]
-    fn initialise_device(device: &HidDevice) -> u32 {
-        let mut buf = [0u8; RIGHT_SIZE];
-        let mut obuf: [u8; 2] = [0, NOSERED];
-        device.send_feature_report(&obuf).unwrap(); // nose light set red
-        println!("Operate a key on the Saitek Switch");
-        let rsize = device.read(&mut buf).unwrap_or_else(|_| {
-            println!("Saitek Switch read error");
-            process::exit(3);
-        });
-        let reply: u32 = if rsize > 2 { Self::pack(&buf) } else { 0 };
+    /// Sets the three gear LEDs
+    pub fn set_leds(&mut self, leds: GearLeds) -> io::Result<()> {
+        self.source.send_feature_report(&[0, leds.encode()])?;
+        self.leds = leds;
+        Ok(())
+    }
 
-        obuf[1] = ALLOFF;
-        device.send_feature_report(&obuf).unwrap(); // all leds off
-        println!("Saitek Switch ready");
+    /// Returns the last setting of the gear LEDs
+    pub fn get_leds(&self) -> GearLeds {
+        self.leds
+    }
 
-        reply
+    /// Returns a handle for setting the gear LEDs from another thread
+    pub fn led_control(&self) -> LedControl {
+        self.led_control.clone()
+    }
+
+    /// Sends the LED setting requested through 'LedControl', if any
+    fn send_requested_leds(&mut self) -> io::Result<()> {
+        match self.led_control.take() {
+            Some(leds) => self.set_leds(leds),
+            None => Ok(()),
+        }
     }
 
-    /// Waits for a key to be pressed.
-    /// When key is received, returns the
+    //----------------------------------------------------------------------------------------
+
+    /// Sends a RED led to the nosewheel, waiting for a key to be pressed.
+    /// When key is received, clears the led, and returns the
     /// value read from the device, which gives the current switch settings.
-    #[cfg(piped)]
-    fn initialise_device() -> u32 {
-        let reply: u32;
+    fn initialise_device(&mut self) -> io::Result<u32> {
         let mut buf = [0u8; RIGHT_SIZE];
+        self.set_leds(GearLeds {
+            nose: LedColour::Red,
+            ..GearLeds::OFF
+        })?; // nose light set red
         println!("Operate a key on the Saitek Switch");
-        let rsize = io::stdin().read(&mut buf).unwrap();
-        if rsize > 2 {
-            reply = Self::pack(&buf);
-        } else {
-            reply = 0;
-        }
+        let rsize = self
+            .source
+            .read(&mut buf)
+            .map_err(|e| io::Error::new(e.kind(), format!("Saitek Switch read error: {}", e)))?;
+        let reply: u32 = if rsize > 2 { Self::pack(&buf) } else { 0 };
+
+        self.set_leds(GearLeds::OFF)?; // all leds off
         println!("Saitek Switch ready 0x{:06x}", reply);
 
-        reply
+        Ok(reply)
     }
 
     //-------------------------------------------------------------------------------------------------------
@@ -248,27 +421,122 @@ impl Device {
         // println!("pack: {:?}", buf);
         (buf[0] as u32) << 16 | (buf[1] as u32) << 8 | (buf[2] as u32)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use switch_constants::*;
+
+    use std::collections::VecDeque;
+
+    /// Reports given by a test, then the end of the input
+    struct Reports(VecDeque<u32>);
+
+    impl InputSource for Reports {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(report) => {
+                    buf[..3].copy_from_slice(&report.to_be_bytes()[1..]);
+                    Ok(buf.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    /// A device whose first report is the initial settings, and the events
+    /// decoded from each of the rest
+    fn events(reports: &[u32]) -> Vec<Vec<EventKind>> {
+        let source = Reports(reports.iter().copied().collect());
+        let mut device = Device::new(Box::new(source)).unwrap();
+        let mut events = Vec::new();
+        while let Ok(status) = device.read() {
+            match status {
+                ReadStatus::Report(report) => {
+                    events.push(report.iter().map(|event| event.kind).collect())
+                }
+                ReadStatus::Reconnected(_) => panic!("unexpected reconnection"),
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn switch_changes() {
+        let start = MAGOFF | GEARDOWN;
+        assert_eq!(
+            events(&[start, start | BATTERY, start | BATTERY | TAXI, start | TAXI]),
+            vec![
+                vec![EventKind::Switch {
+                    id: SwitchId::Battery,
+                    on: true
+                }],
+                vec![EventKind::Switch {
+                    id: SwitchId::Taxi,
+                    on: true
+                }],
+                vec![EventKind::Switch {
+                    id: SwitchId::Battery,
+                    on: false
+                }],
+            ]
+        );
+    }
+
+    #[test]
+    fn magneto_start_returns_to_both() {
+        // turned to START and released: BOTH, START, BOTH, with no position in between
+        let start = MAGBOTH | GEARDOWN;
+        assert_eq!(
+            events(&[start, GEARDOWN, MAGSTART | GEARDOWN, GEARDOWN, start]),
+            vec![
+                vec![],
+                vec![EventKind::Magneto(MagnetoPosition::Start)],
+                vec![],
+                vec![EventKind::Magneto(MagnetoPosition::Both)],
+            ]
+        );
+    }
+
+    #[test]
+    fn magneto_position_passed_through() {
+        let start = MAGOFF | GEARDOWN;
+        assert_eq!(
+            events(&[start, MAGR | GEARDOWN, MAGL | GEARDOWN, start]),
+            vec![
+                vec![EventKind::Magneto(MagnetoPosition::Right)],
+                vec![EventKind::Magneto(MagnetoPosition::Left)],
+                vec![EventKind::Magneto(MagnetoPosition::Off)],
+            ]
+        );
+    }
+
+    #[test]
+    fn gear_lever_transitions() {
+        // the lever is in neither position while moving
+        assert_eq!(
+            events(&[
+                MAGOFF | GEARDOWN,
+                MAGOFF,
+                MAGOFF | GEARUP,
+                MAGOFF,
+                MAGOFF | GEARDOWN
+            ]),
+            vec![
+                vec![],
+                vec![EventKind::GearLever(GearPosition::Up)],
+                vec![],
+                vec![EventKind::GearLever(GearPosition::Down)],
+            ]
+        );
+    }
 
-    /// Create a hashmap keyed by the (text) switch name, with the switch bit-value as the data.
-    /// This data is all static information.
-    ///
-    pub fn make_name_map() -> HashMap<String, u32> {
-        let mut devmap: HashMap<String, u32> = HashMap::new();
-        devmap.insert("BATTERY".to_string(), BATTERY);
-        devmap.insert("ALT".to_string(), ALT);
-        devmap.insert("AVIONICS".to_string(), AVIONICS);
-        devmap.insert("FUELPUMP".to_string(), FUELPUMP);
-        devmap.insert("DEICE".to_string(), DEICE);
-        devmap.insert("PITOTHEAT".to_string(), PITOTHEAT);
-        devmap.insert("COWLCLOSE".to_string(), COWLCLOSE);
-        devmap.insert("PANELLIGHT".to_string(), PANELLIGHT);
-        devmap.insert("BEACON".to_string(), BEACON);
-        devmap.insert("NAVLIGHTS".to_string(), NAVLIGHTS);
-        devmap.insert("STROBE".to_string(), STROBE);
-        devmap.insert("TAXI".to_string(), TAXI);
-        devmap.insert("LANDING".to_string(), LANDING);
-        devmap.insert("GEARUP".to_string(), GEARUP);
-        devmap.insert("GEARDOWN".to_string(), GEARDOWN);
-        devmap
+    #[test]
+    fn gear_lever_returned_without_reaching_position() {
+        assert_eq!(
+            events(&[MAGOFF | GEARDOWN, MAGOFF, MAGOFF | GEARDOWN]),
+            vec![vec![], vec![]]
+        );
     }
 }
@@ -0,0 +1,244 @@
+//!
+//! Recording and replaying Switch Panel sessions.
+//!
+//! A recording is a text file with one line per report read from the device:
+//! the time it arrived in milliseconds since recording started, and the 3 report bytes in hex.
+//!     # saitekswitch recording
+//!     0 012008
+//!     1520 032008
+//! Lines starting with '#' are comments.
+//!
+
+use crate::input::InputSource;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Writes every report to a recording file
+pub struct Recorder<W: Write = LineWriter<File>> {
+    file: W,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Create (or replace) the recording file
+    pub fn create(path: &str) -> io::Result<Recorder> {
+        let file = File::create(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Unable to create recording file '{}': {}", path, e),
+            )
+        })?;
+        Recorder::new(LineWriter::new(file))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording written to 'file'
+    fn new(mut file: W) -> io::Result<Recorder<W>> {
+        writeln!(file, "# saitekswitch recording")?;
+        Ok(Recorder {
+            file,
+            start: Instant::now(),
+        })
+    }
+
+    /// Record one report, packed as in 'Device', which arrived at 'time'
+    pub fn record(&mut self, report: u32, time: Instant) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "{} {:06x}",
+            time.saturating_duration_since(self.start).as_millis(),
+            report
+        )
+    }
+}
+
+//-------------------------------------------------------------------------------
+
+/// Reports replayed from a recording file, with the original timing or as fast as possible
+pub struct ReplayInput {
+    lines: io::Lines<Box<dyn BufRead>>,
+    line_number: usize,
+    fast: bool,
+    last: Option<(u64, Instant)>, // time of the previous report in the file, and when replayed
+    held: Option<(u64, u32)>,     // next report, waiting until it is due
+}
+
+impl ReplayInput {
+    pub fn open(path: &str, fast: bool) -> io::Result<ReplayInput> {
+        let file = File::open(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Unable to open recording file '{}': {}", path, e),
+            )
+        })?;
+        Ok(ReplayInput::new(Box::new(BufReader::new(file)), fast))
+    }
+
+    /// Replay the recording read from 'reader'
+    fn new(reader: Box<dyn BufRead>, fast: bool) -> ReplayInput {
+        ReplayInput {
+            lines: reader.lines(),
+            line_number: 0,
+            fast,
+            last: None,
+            held: None,
+        }
+    }
+
+    /// The next report in the file, as (time, report), or None at the end
+    fn next_record(&mut self) -> io::Result<Option<(u64, u32)>> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            self.line_number += 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return match parse_record(line) {
+                Some(record) => Ok(Some(record)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid recording at line {}: '{}'", self.line_number, line),
+                )),
+            };
+        }
+        Ok(None)
+    }
+}
+
+/// A recording line as (time, report): the milliseconds, and the 3 report bytes
+/// as 6 hex digits. None if the line is not in that form.
+fn parse_record(line: &str) -> Option<(u64, u32)> {
+    let mut fields = line.split_whitespace();
+    let time = fields.next()?;
+    let report = fields.next()?;
+    if fields.next().is_some()
+        || !time.bytes().all(|b| b.is_ascii_digit())
+        || report.len() != 6
+        || !report.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    Some((time.parse().ok()?, u32::from_str_radix(report, 16).ok()?))
+}
+
+impl InputSource for ReplayInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(length) = self.read_timeout(buf, Duration::from_secs(1))? {
+                return Ok(length);
+            }
+        }
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<Option<usize>> {
+        if self.held.is_none() {
+            self.held = self.next_record()?;
+        }
+        let (time, report) = match self.held {
+            Some(record) => record,
+            None => return Ok(Some(0)),
+        };
+        // wait for the report to be due, keeping the spacing in the file
+        if let Some((last_time, replayed)) = self.last {
+            if !self.fast && time > last_time {
+                let due = replayed + Duration::from_millis(time - last_time);
+                let wait = due.saturating_duration_since(Instant::now());
+                if wait > timeout {
+                    thread::sleep(timeout);
+                    return Ok(None);
+                }
+                thread::sleep(wait);
+            }
+        }
+        self.held = None;
+        self.last = Some((time, Instant::now()));
+        let bytes = report.to_be_bytes();
+        let length = buf.len().min(3);
+        buf[..length].copy_from_slice(&bytes[1..1 + length]);
+        Ok(Some(buf.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replay 'text' as fast as possible
+    fn replay(text: &'static str) -> ReplayInput {
+        ReplayInput::new(Box::new(text.as_bytes()), true)
+    }
+
+    #[test]
+    fn record_lines() {
+        assert_eq!(parse_record("0 012008"), Some((0, 0x012008)));
+        assert_eq!(parse_record("1520\t0320Ab"), Some((1520, 0x0320ab)));
+        assert_eq!(parse_record("  7   ffffff  "), Some((7, 0xffffff)));
+    }
+
+    #[test]
+    fn malformed_record_lines() {
+        for line in [
+            "",
+            "0",
+            "012008",
+            "0 12008",    // odd length
+            "0 0012008",  // odd length
+            "0 01200800", // too long
+            "0 01200g",
+            "0 +12008",
+            "-1 012008",
+            "+1 012008",
+            "x 012008",
+            "1.5 012008",
+            "0 012008 1",
+            "0 0x1200",
+            "99999999999999999999 012008",
+        ] {
+            assert_eq!(parse_record(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn record_arrival_times() {
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+        let start = recorder.start;
+        recorder.record(0x012004, start).unwrap();
+        recorder
+            .record(0x032004, start + Duration::from_millis(500))
+            .unwrap();
+        // a report which arrived as the recording started
+        recorder
+            .record(0x012004, start - Duration::from_millis(1))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(recorder.file).unwrap(),
+            "# saitekswitch recording\n0 012004\n500 032004\n0 012004\n"
+        );
+    }
+
+    #[test]
+    fn replay_reports() {
+        let mut replay = replay("# saitekswitch recording\n0 012008\n\n# comment\n250 032004\n");
+        let mut buf = [0u8; 4];
+        assert_eq!(replay.read(&mut buf).unwrap(), 4);
+        assert_eq!(buf[..3], [0x01, 0x20, 0x08]);
+        assert_eq!(replay.read(&mut buf).unwrap(), 4);
+        assert_eq!(buf[..3], [0x03, 0x20, 0x04]);
+        assert_eq!(replay.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn replay_reports_the_bad_line() {
+        let mut replay = replay("# saitekswitch recording\n0 012008\n10 12008\n");
+        let mut buf = [0u8; 4];
+        assert_eq!(replay.read(&mut buf).unwrap(), 4);
+        let error = replay.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Invalid recording at line 3: '10 12008'");
+    }
+}
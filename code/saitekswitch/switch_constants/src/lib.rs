@@ -30,7 +30,6 @@ THE SOFTWARE.
 //!      BATTERY, ALT, AVIONICS,FUELPUMP, DEICE, PITOTHEAT, COWLCLOSE, PANELLIGHT,
 //!      BEACON, NAVLIGHTS, STROBE, TAXI, LANDING
 //!   rotary Magneto switch
-//!
 //!      MAGOFF, MAGR, MAGL, MAGBOTH, MAGSTART
 //!   a gear lever, up or down
 //!   3 leds (R/O/G) for status indication
@@ -91,17 +90,11 @@ pub const MAGMASK: u32 = MAGOFF | MAGR | MAGL | MAGBOTH | MAGSTART;
 
 // Write data for Gear LEDS
 // LED may be yellow if RED and GREEN asserted
-#[cfg(not(piped))]
 pub const NOSERED: u8 = 0x08;
-// pub const NOSEGREEN: u8 = 0x01;
-// pub const NOSEOFF: u8 = !(NOSERED | NOSEGREEN);
-// pub const LEFTRED: u8 = 0x10;
-
-// pub const LEFTGREEN: u8 = 0x02;
-// pub const LEFTOFF: u8 = !(LEFTRED | LEFTGREEN);
-// pub const RIGHTRED: u8 = 0x20;
-// pub const RIGHTGREEN: u8 = 0x04;
-// pub const RIGHTOFF: u8 = !(RIGHTRED | RIGHTGREEN);
-#[cfg(not(piped))]
+pub const NOSEGREEN: u8 = 0x01;
+pub const LEFTRED: u8 = 0x10;
+pub const LEFTGREEN: u8 = 0x02;
+pub const RIGHTRED: u8 = 0x20;
+pub const RIGHTGREEN: u8 = 0x04;
 pub const ALLOFF: u8 = 0x00;
 // pub const LEDFIELD: u8 = 0x3f;